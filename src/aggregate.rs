@@ -0,0 +1,278 @@
+use crate::path::{KeyPath, PathError};
+use crate::value::{compare_values, display_value, number_value};
+use num_format::{Locale, ToFormattedString};
+use serde_json::{Map, Value};
+use std::{borrow::Borrow, cmp::Ordering, collections::HashMap, fmt};
+
+/// An aggregate function over the records of a group
+#[derive(Debug, Clone)]
+pub enum Aggregate {
+    /// `count`: number of records in the group
+    Count,
+    /// `count(path)`: number of records with a non-null value at `path`
+    CountOf(KeyPath),
+    Sum(KeyPath),
+    Avg(KeyPath),
+    Min(KeyPath),
+    Max(KeyPath),
+}
+
+impl Aggregate {
+    pub fn parse(spec: &str) -> Result<Self, PathError> {
+        let spec = spec.trim();
+        if spec == "count" || spec == "count(*)" {
+            return Ok(Aggregate::Count);
+        }
+
+        let err = |message: &str| PathError {
+            path: spec.to_string(),
+            message: message.to_string(),
+        };
+        let (name, rest) = spec
+            .split_once('(')
+            .ok_or_else(|| err("expected count or FUNCTION(path)"))?;
+        let path = rest.strip_suffix(')').ok_or_else(|| err("missing ')'"))?;
+        let path = KeyPath::parse_single(path.trim())?;
+
+        match name.trim() {
+            "count" => Ok(Aggregate::CountOf(path)),
+            "sum" => Ok(Aggregate::Sum(path)),
+            "avg" => Ok(Aggregate::Avg(path)),
+            "min" => Ok(Aggregate::Min(path)),
+            "max" => Ok(Aggregate::Max(path)),
+            _ => Err(err("unknown aggregate (use count, sum, avg, min or max)")),
+        }
+    }
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Aggregate::Count => write!(f, "count"),
+            Aggregate::CountOf(p) => write!(f, "count({})", p),
+            Aggregate::Sum(p) => write!(f, "sum({})", p),
+            Aggregate::Avg(p) => write!(f, "avg({})", p),
+            Aggregate::Min(p) => write!(f, "min({})", p),
+            Aggregate::Max(p) => write!(f, "max({})", p),
+        }
+    }
+}
+
+/// Running state of one aggregate within one group
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count(usize),
+    Sum { total: f64, seen: usize },
+    Extreme(Option<Value>),
+}
+
+impl Accumulator {
+    fn new(aggregate: &Aggregate) -> Self {
+        match aggregate {
+            Aggregate::Count | Aggregate::CountOf(_) => Accumulator::Count(0),
+            Aggregate::Sum(_) | Aggregate::Avg(_) => Accumulator::Sum {
+                total: 0.0,
+                seen: 0,
+            },
+            Aggregate::Min(_) | Aggregate::Max(_) => Accumulator::Extreme(None),
+        }
+    }
+
+    fn update(&mut self, aggregate: &Aggregate, record: &Value) {
+        match (self, aggregate) {
+            (Accumulator::Count(n), Aggregate::Count) => *n += 1,
+            (Accumulator::Count(n), Aggregate::CountOf(path))
+                if path.get(record).is_some_and(|v| !v.is_null()) =>
+            {
+                *n += 1
+            }
+            (Accumulator::Sum { total, seen }, Aggregate::Sum(path) | Aggregate::Avg(path)) => {
+                if let Some(x) = path.get(record).and_then(Value::as_f64) {
+                    *total += x;
+                    *seen += 1;
+                }
+            }
+            (Accumulator::Extreme(current), Aggregate::Min(path) | Aggregate::Max(path)) => {
+                let Some(value) = path.get(record).filter(|v| !v.is_null()) else {
+                    return;
+                };
+                let wanted = if matches!(aggregate, Aggregate::Min(_)) {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                };
+                let replace = current
+                    .as_ref()
+                    .is_none_or(|c| compare_values(value, c) == wanted);
+                if replace {
+                    *current = Some(value.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn result(&self, aggregate: &Aggregate) -> Value {
+        match (self, aggregate) {
+            (Accumulator::Count(n), _) => Value::from(*n),
+            (Accumulator::Sum { total, .. }, Aggregate::Sum(_)) => number_value(*total),
+            (Accumulator::Sum { seen: 0, .. }, _) => Value::Null,
+            (Accumulator::Sum { total, seen }, _) => number_value(*total / *seen as f64),
+            (Accumulator::Extreme(value), _) => value.clone().unwrap_or(Value::Null),
+        }
+    }
+}
+
+/// Group-by aggregation, e.g. `count, avg(age), max(timestamp)` grouped by `gender`.
+///
+/// Records missing a group key are collected in an explicit `null` group.
+#[derive(Debug, Clone)]
+pub struct GroupBy {
+    keys: Vec<KeyPath>,
+    aggregates: Vec<Aggregate>,
+}
+
+impl GroupBy {
+    /// `aggregates` is a comma-separated list such as `count, avg(age)`.
+    pub fn parse<S: AsRef<str>>(keys: &[S], aggregates: &str) -> Result<Self, PathError> {
+        let keys = keys
+            .iter()
+            .map(|k| KeyPath::parse_single(k.as_ref().trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let aggregates = aggregates
+            .split(',')
+            .filter(|a| !a.trim().is_empty())
+            .map(Aggregate::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { keys, aggregates })
+    }
+
+    pub fn run<I>(&self, records: I) -> GroupByResult
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+    {
+        // Keyed by the serialized group values, which are not hashable as `Value`
+        let mut groups: HashMap<String, (Vec<Value>, Vec<Accumulator>)> = HashMap::new();
+
+        for record in records {
+            let record = record.borrow();
+            let group: Vec<Value> = self
+                .keys
+                .iter()
+                .map(|k| k.get(record).cloned().unwrap_or(Value::Null))
+                .collect();
+            let id = Value::Array(group.clone()).to_string();
+            let (_, accumulators) = groups.entry(id).or_insert_with(|| {
+                (
+                    group,
+                    self.aggregates.iter().map(Accumulator::new).collect(),
+                )
+            });
+            for (accumulator, aggregate) in accumulators.iter_mut().zip(&self.aggregates) {
+                accumulator.update(aggregate, record);
+            }
+        }
+
+        let mut rows: Vec<Vec<Value>> = groups
+            .into_values()
+            .map(|(mut group, accumulators)| {
+                group.extend(
+                    accumulators
+                        .iter()
+                        .zip(&self.aggregates)
+                        .map(|(acc, agg)| acc.result(agg)),
+                );
+                group
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            a[..self.keys.len()]
+                .iter()
+                .zip(&b[..self.keys.len()])
+                .map(|(x, y)| compare_values(x, y))
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        GroupByResult {
+            columns: self
+                .keys
+                .iter()
+                .map(|k| k.to_string())
+                .chain(self.aggregates.iter().map(|a| a.to_string()))
+                .collect(),
+            rows,
+        }
+    }
+}
+
+/// Table of aggregated groups, one row per group sorted by group key
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupByResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl GroupByResult {
+    /// One JSON object per group, keyed by column name
+    pub fn to_records(&self) -> Vec<Value> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let map: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .cloned()
+                    .zip(row.iter().cloned())
+                    .collect();
+                Value::Object(map)
+            })
+            .collect()
+    }
+
+    pub fn show_table(&self, source_name: &str) {
+        println!("===============================");
+        println!(
+            "{} groups in {}",
+            self.rows.len().to_formatted_string(&Locale::en),
+            source_name
+        );
+
+        let cells: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(display_value).collect())
+            .collect();
+        let widths: Vec<usize> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].len())
+                    .chain([c.len()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+
+        let line = |values: &[String]| {
+            values
+                .iter()
+                .zip(&widths)
+                .map(|(v, w)| format!("{:<width$}", v, width = w))
+                .collect::<Vec<_>>()
+                .join("  ")
+        };
+        println!("{}", line(&self.columns));
+        println!(
+            "{}",
+            "-".repeat(widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1))
+        );
+        for row in &cells {
+            println!("{}", line(row));
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use jsonl_tools::run; // Replace `your_crate_name` with your actual crate name
+use std::process::ExitCode;
 
-fn main() {
-    run(); // This will execute your refactored code
+fn main() -> ExitCode {
+    run() // This will execute your refactored code
 }
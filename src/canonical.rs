@@ -0,0 +1,257 @@
+use crate::jsonl::key_frequencies;
+use crate::path::{child_path, element_path, without_indices};
+use serde_json::Value;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    fmt::Write as _,
+    io::{self, BufRead, Write},
+};
+
+/// Order of the keys of every object in canonical output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyOrder {
+    /// Alphabetical (by Unicode code point)
+    Sorted,
+    /// Most frequent key paths first, ties alphabetical
+    Frequency,
+    /// The listed key paths first, in the given order; other keys follow alphabetically.
+    /// Nested keys are dotted (`user.name`), keys inside arrays use `[]` (`items[].id`).
+    Keys(Vec<String>),
+}
+
+impl KeyOrder {
+    pub fn parse(spec: &str) -> Self {
+        match spec {
+            "sorted" => KeyOrder::Sorted,
+            "frequency" => KeyOrder::Frequency,
+            keys => KeyOrder::Keys(keys.split(',').map(String::from).collect()),
+        }
+    }
+}
+
+/// Writes JSON in one canonical form: compact separators, keys in a fixed
+/// order, numbers as serde_json prints them (`1.0`, `100.0` for `1e2`), and
+/// only the escapes JSON requires, or every non-ASCII character as `\uXXXX`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Canonicalizer {
+    /// Rank of each key path; unranked keys sort alphabetically after them
+    ranks: HashMap<String, usize>,
+    pub ascii: bool,
+}
+
+impl Canonicalizer {
+    /// Builds the formatter for `order`; [`KeyOrder::Frequency`] ranks the key
+    /// paths of `records`, the other orders ignore them.
+    pub fn new<I>(order: &KeyOrder, records: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+    {
+        let ranks = match order {
+            KeyOrder::Sorted => HashMap::new(),
+            KeyOrder::Keys(keys) => keys
+                .iter()
+                .enumerate()
+                .map(|(rank, key)| (key.clone(), rank))
+                .collect(),
+            KeyOrder::Frequency => {
+                // Elements share one rank, from their most frequent index
+                let mut ranks = HashMap::new();
+                for (rank, (path, _)) in key_frequencies(records).into_iter().enumerate() {
+                    ranks.entry(without_indices(&path)).or_insert(rank);
+                }
+                ranks
+            }
+        };
+        Self {
+            ranks,
+            ascii: false,
+        }
+    }
+
+    pub fn format(&self, value: &Value) -> String {
+        let mut out = String::new();
+        self.write_value(value, "", &mut out);
+        out
+    }
+
+    fn write_value(&self, value: &Value, path: &str, out: &mut String) {
+        match value {
+            Value::Object(map) => {
+                let mut entries: Vec<(&String, &Value, String)> = map
+                    .iter()
+                    .map(|(key, child)| (key, child, child_path(path, key)))
+                    .collect();
+                entries.sort_by(|a, b| {
+                    let rank = |path: &String| self.ranks.get(path).copied().unwrap_or(usize::MAX);
+                    rank(&a.2).cmp(&rank(&b.2)).then_with(|| a.0.cmp(b.0))
+                });
+                out.push('{');
+                for (i, (key, child, child_path)) in entries.into_iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    self.write_string(key, out);
+                    out.push(':');
+                    self.write_value(child, &child_path, out);
+                }
+                out.push('}');
+            }
+            Value::Array(items) => {
+                let item_path = element_path(path);
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    self.write_value(item, &item_path, out);
+                }
+                out.push(']');
+            }
+            Value::String(s) => self.write_string(s, out),
+            other => out.push_str(&other.to_string()),
+        }
+    }
+
+    fn write_string(&self, s: &str, out: &mut String) {
+        let escaped = Value::from(s).to_string();
+        if !self.ascii {
+            out.push_str(&escaped);
+            return;
+        }
+        for c in escaped.chars() {
+            if c.is_ascii() {
+                out.push(c);
+            } else {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    let _ = write!(out, "\\u{:04x}", unit);
+                }
+            }
+        }
+    }
+}
+
+/// Outcome of formatting a JSONL input
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FmtSummary {
+    pub rows: usize,
+    /// Line numbers (from 1) that were not canonical, blank lines included
+    pub changed_lines: Vec<usize>,
+    /// Line numbers holding a number that changes value when rewritten, such
+    /// as integers beyond 64 bits or decimals with more digits than an f64 keeps
+    pub lossy_lines: Vec<usize>,
+}
+
+impl FmtSummary {
+    pub fn is_canonical(&self) -> bool {
+        self.changed_lines.is_empty()
+    }
+}
+
+impl Canonicalizer {
+    /// Rewrites every line of `input` canonically to `output` (or only compares
+    /// when it is `None`, for `--check`). Blank lines are dropped and CRLF
+    /// line endings become LF.
+    pub fn format_lines<R, W>(&self, input: R, mut output: Option<W>) -> io::Result<FmtSummary>
+    where
+        R: BufRead,
+        W: Write,
+    {
+        let mut summary = FmtSummary::default();
+        for (i, line) in input.split(b'\n').enumerate() {
+            let line = String::from_utf8(line?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if line.trim().is_empty() {
+                summary.changed_lines.push(i + 1);
+                continue;
+            }
+            let record: Value = serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, e))
+            })?;
+            let canonical = self.format(&record);
+            if canonical != line {
+                summary.changed_lines.push(i + 1);
+                if !number_literals(&line).all(number_round_trips) {
+                    summary.lossy_lines.push(i + 1);
+                }
+            }
+            if let Some(writer) = output.as_mut() {
+                writer.write_all(canonical.as_bytes())?;
+                writer.write_all(b"\n")?;
+            }
+            summary.rows += 1;
+        }
+        if let Some(writer) = output.as_mut() {
+            writer.flush()?;
+        }
+        Ok(summary)
+    }
+}
+
+/// The number literals of a JSON text, outside its strings
+fn number_literals(json: &str) -> impl Iterator<Item = &str> {
+    let bytes = json.as_bytes();
+    let mut i = 0;
+    let mut in_string = false;
+    std::iter::from_fn(move || {
+        while i < bytes.len() {
+            let b = bytes[i];
+            if in_string {
+                match b {
+                    b'\\' => i += 1,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+            } else if b == b'"' {
+                in_string = true;
+            } else if b == b'-' || b.is_ascii_digit() {
+                let start = i;
+                while i < bytes.len()
+                    && matches!(bytes[i], b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E')
+                {
+                    i += 1;
+                }
+                return Some(&json[start..i]);
+            }
+            i += 1;
+        }
+        None
+    })
+}
+
+/// Sign, significant digits and decimal exponent of a number literal, so
+/// that `1e2`, `100` and `100.0` compare equal
+fn decimal_parts(literal: &str) -> (bool, String, i64) {
+    let (mantissa, exponent) = match literal.find(['e', 'E']) {
+        Some(at) => (&literal[..at], literal[at + 1..].parse().unwrap_or(0)),
+        None => (literal, 0),
+    };
+    let negative = mantissa.starts_with('-');
+    let mantissa = mantissa.trim_start_matches('-');
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac);
+    let significant = digits.trim_start_matches('0');
+    let point = exponent + int.len() as i64 - (digits.len() - significant.len()) as i64;
+    let significant = significant.trim_end_matches('0');
+    if significant.is_empty() {
+        (false, String::new(), 0)
+    } else {
+        (negative, significant.to_string(), point)
+    }
+}
+
+/// Whether serde_json reads `literal` without changing its value
+fn number_round_trips(literal: &str) -> bool {
+    if !literal.contains(['.', 'e', 'E'])
+        && (literal.parse::<i64>().is_ok() || literal.parse::<u64>().is_ok())
+    {
+        return true;
+    }
+    match literal.parse::<f64>() {
+        Ok(float) if float.is_finite() => {
+            decimal_parts(literal) == decimal_parts(&format!("{:e}", float))
+        }
+        _ => false,
+    }
+}
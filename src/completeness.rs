@@ -0,0 +1,153 @@
+use crate::path::without_indices;
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+/// State of one key path in one row
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStatus {
+    Present,
+    /// The key is there with a `null` value
+    Null,
+    /// The parent object is there but lacks the key
+    Absent,
+    /// The parent is absent, `null` or not an object, or the path is an array
+    /// element beyond the end of a shorter array: the key cannot be expected
+    NotApplicable,
+}
+
+/// JSON type at a path, as far as the classification needs it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Object,
+    Null,
+    Other,
+}
+
+fn collect_path_kinds(value: &Value, prefix: &str, kinds: &mut HashMap<String, Kind>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                collect_path_kinds(child, &path, kinds);
+                kinds.insert(path, kind_of(child));
+            }
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                let path = format!("{}[{}]", prefix, i);
+                collect_path_kinds(item, &path, kinds);
+                kinds.insert(path, kind_of(item));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn kind_of(value: &Value) -> Kind {
+    match value {
+        Value::Object(_) => Kind::Object,
+        Value::Null => Kind::Null,
+        _ => Kind::Other,
+    }
+}
+
+/// Splits `a.b[0].c` into its parent (`a.b[0]`) and whether the last step is
+/// an object key (rather than an array index).
+fn parent_of(path: &str) -> (&str, bool) {
+    if path.ends_with(']')
+        && let Some(open) = path.rfind('[')
+    {
+        return (&path[..open], false);
+    }
+    match path.rfind('.') {
+        Some(dot) => (&path[..dot], true),
+        None => ("", true),
+    }
+}
+
+/// Which keys a row must have to be complete. Only required keys that are
+/// absent from a present parent object make a row incomplete (and null ones
+/// too with `null_is_missing`); children of an absent or `null` parent are
+/// not applicable rather than missing.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Completeness {
+    /// Key paths that may be absent or null; `[]` matches any array index
+    /// (`items[].discount`)
+    pub optional: HashSet<String>,
+    pub null_is_missing: bool,
+}
+
+impl Completeness {
+    pub fn new(optional: impl IntoIterator<Item = String>, null_is_missing: bool) -> Self {
+        Self {
+            optional: optional.into_iter().collect(),
+            null_is_missing,
+        }
+    }
+
+    pub fn is_optional(&self, path: &str) -> bool {
+        !self.optional.is_empty()
+            && (self.optional.contains(path) || self.optional.contains(&without_indices(path)))
+    }
+
+    /// Status of each of `all_keys` in `row`.
+    pub fn statuses<'k, I>(row: &Value, all_keys: I) -> Vec<(&'k str, KeyStatus)>
+    where
+        I: IntoIterator<Item = &'k String>,
+    {
+        let mut kinds = HashMap::new();
+        collect_path_kinds(row, "", &mut kinds);
+
+        all_keys
+            .into_iter()
+            .map(|path| {
+                let status = match kinds.get(path.as_str()) {
+                    Some(Kind::Null) => KeyStatus::Null,
+                    Some(_) => KeyStatus::Present,
+                    None => {
+                        let (parent, is_key) = parent_of(path);
+                        let parent_is_object = if parent.is_empty() {
+                            row.is_object()
+                        } else {
+                            kinds.get(parent) == Some(&Kind::Object)
+                        };
+                        if is_key && parent_is_object {
+                            KeyStatus::Absent
+                        } else {
+                            KeyStatus::NotApplicable
+                        }
+                    }
+                };
+                (path.as_str(), status)
+            })
+            .collect()
+    }
+
+    /// Required keys of `all_keys` that `row` lacks and that are null in it,
+    /// each sorted. `None` when the row is complete.
+    pub fn check(
+        &self,
+        row: &Value,
+        all_keys: &HashSet<String>,
+    ) -> Option<(Vec<String>, Vec<String>)> {
+        let mut missing = Vec::new();
+        let mut null = Vec::new();
+        for (path, status) in Self::statuses(row, all_keys) {
+            match status {
+                KeyStatus::Absent if !self.is_optional(path) => missing.push(path.to_string()),
+                KeyStatus::Null if !self.is_optional(path) => null.push(path.to_string()),
+                _ => {}
+            }
+        }
+        if missing.is_empty() && (null.is_empty() || !self.null_is_missing) {
+            return None;
+        }
+        missing.sort();
+        null.sort();
+        Some((missing, null))
+    }
+}
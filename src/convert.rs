@@ -0,0 +1,170 @@
+use crate::path::KeyPath;
+use crate::project::flatten_objects_into;
+use serde_json::{Map, Value};
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+/// Output format of `convert`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertTo {
+    Jsonl,
+    Table(TableFormat),
+    /// Apache Parquet, written to a file (see [`crate::parquet_export`])
+    Parquet,
+}
+
+impl ConvertTo {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "jsonl" => Some(ConvertTo::Jsonl),
+            "parquet" => Some(ConvertTo::Parquet),
+            other => TableFormat::parse(other).map(ConvertTo::Table),
+        }
+    }
+}
+
+/// Tabular formats, for `convert` output and CSV/TSV input
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableFormat {
+    /// RFC 4180: cells with commas, quotes or line breaks are quoted
+    Csv,
+    /// Tab-separated; tabs, line breaks and backslashes in cells are escaped as `\t`, `\n`, `\r`, `\\`
+    Tsv,
+}
+
+impl TableFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "csv" => Some(TableFormat::Csv),
+            "tsv" => Some(TableFormat::Tsv),
+            _ => None,
+        }
+    }
+
+    fn delimiter(&self) -> char {
+        match self {
+            TableFormat::Csv => ',',
+            TableFormat::Tsv => '\t',
+        }
+    }
+
+    fn escape(&self, cell: &str) -> String {
+        match self {
+            TableFormat::Csv => {
+                if cell.contains([',', '"', '\n', '\r']) {
+                    format!("\"{}\"", cell.replace('"', "\"\""))
+                } else {
+                    cell.to_string()
+                }
+            }
+            TableFormat::Tsv => cell
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+        }
+    }
+}
+
+/// Exports records as a table with one column per key path.
+///
+/// Nested objects are flattened into dotted columns (`user.name`); arrays,
+/// and objects where a column stops (`--columns=user` on `{"user": {...}}`),
+/// are written as JSON in the cell. Strings
+/// are written as-is, `null` as `null`, and missing keys leave the cell empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableExport {
+    pub format: TableFormat,
+    pub columns: Vec<String>,
+}
+
+fn flatten_record(record: &Value) -> Map<String, Value> {
+    let mut flat = Map::new();
+    flatten_objects_into("", record, &mut flat);
+    // A record that is not an object has no named columns
+    flat.remove("");
+    flat
+}
+
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+impl TableExport {
+    /// Uses every column found in the records, most frequent first (ties by name).
+    pub fn discover<I>(format: TableFormat, records: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+    {
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for record in records {
+            for key in flatten_record(record.borrow())
+                .into_iter()
+                .map(|(key, _)| key)
+            {
+                *counts.entry(key).or_insert(0) += 1;
+            }
+        }
+        let mut columns: Vec<(String, usize)> = counts.into_iter().collect();
+        columns.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        Self {
+            format,
+            columns: columns.into_iter().map(|(key, _)| key).collect(),
+        }
+    }
+
+    fn write_row<W: Write>(&self, writer: &mut W, cells: &[String]) -> io::Result<()> {
+        let line: Vec<String> = cells.iter().map(|cell| self.format.escape(cell)).collect();
+        writer.write_all(line.join(&self.format.delimiter().to_string()).as_bytes())?;
+        // CRLF is the CSV standard line break; TSV uses plain newlines
+        match self.format {
+            TableFormat::Csv => writer.write_all(b"\r\n"),
+            TableFormat::Tsv => writer.write_all(b"\n"),
+        }
+    }
+
+    /// Writes the header and one row per record, returning the number of records.
+    pub fn write<I, W>(&self, records: I, mut writer: W) -> io::Result<usize>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+        W: Write,
+    {
+        self.write_row(&mut writer, &self.columns)?;
+        // Columns that are not leaves of the flattened record, such as a
+        // parent object, are looked up as key paths in the record itself
+        let paths: Vec<Option<KeyPath>> = self
+            .columns
+            .iter()
+            .map(|column| KeyPath::parse(column).ok())
+            .collect();
+        let mut rows = 0;
+        for record in records {
+            let record = record.borrow();
+            let flat = flatten_record(record);
+            let cells: Vec<String> = self
+                .columns
+                .iter()
+                .zip(&paths)
+                .map(|(column, path)| {
+                    flat.get(column)
+                        .or_else(|| path.as_ref()?.get(record))
+                        .map(cell_text)
+                        .unwrap_or_default()
+                })
+                .collect();
+            self.write_row(&mut writer, &cells)?;
+            rows += 1;
+        }
+        writer.flush()?;
+        Ok(rows)
+    }
+}
@@ -0,0 +1,228 @@
+use crate::jsonl::JsonlReader;
+use crate::progress::{ProgressCallback, ProgressTracker};
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use serde_json::Value;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::PathBuf,
+};
+
+/// How the JSON documents of a file are laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonLayout {
+    /// One top-level array whose elements are the records: `[{...}, {...}]`
+    Array,
+    /// Whitespace-separated values, possibly pretty-printed over many lines
+    /// (JSONL is the special case of one value per line)
+    Concatenated,
+}
+
+impl fmt::Display for JsonLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonLayout::Array => write!(f, "JSON array"),
+            JsonLayout::Concatenated => write!(f, "concatenated JSON"),
+        }
+    }
+}
+
+impl JsonLayout {
+    /// Skips leading whitespace and peeks at the first byte: `[` starts an array.
+    pub fn detect<R: BufRead>(reader: &mut R) -> io::Result<Self> {
+        loop {
+            let buffer = reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(JsonLayout::Concatenated);
+            }
+            let skip = buffer
+                .iter()
+                .take_while(|b| b.is_ascii_whitespace())
+                .count();
+            if skip < buffer.len() {
+                let layout = if buffer[skip] == b'[' {
+                    JsonLayout::Array
+                } else {
+                    JsonLayout::Concatenated
+                };
+                reader.consume(skip);
+                return Ok(layout);
+            }
+            reader.consume(skip);
+        }
+    }
+}
+
+/// Hands each element of the top-level array to a callback as soon as it is
+/// parsed, so the array itself is never held in memory.
+struct ElementSink<'f, F> {
+    on_record: &'f mut F,
+    count: usize,
+}
+
+impl<'de, F> Visitor<'de> for ElementSink<'_, F>
+where
+    F: FnMut(Value) -> io::Result<()>,
+{
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON array of records")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<usize, A::Error> {
+        while let Some(record) = seq.next_element::<Value>()? {
+            (self.on_record)(record).map_err(de::Error::custom)?;
+            self.count += 1;
+        }
+        Ok(self.count)
+    }
+}
+
+impl<'de, F> DeserializeSeed<'de> for ElementSink<'_, F>
+where
+    F: FnMut(Value) -> io::Result<()>,
+{
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Stream-parses the records of a JSON array or concatenated JSON input,
+/// calling `on_record` for each one, and returns the layout and record count.
+pub fn read_json_documents<R, F>(reader: R, mut on_record: F) -> io::Result<(JsonLayout, usize)>
+where
+    R: Read,
+    F: FnMut(Value) -> io::Result<()>,
+{
+    let mut reader = BufReader::new(reader);
+    let layout = JsonLayout::detect(&mut reader)?;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+    let count = match layout {
+        JsonLayout::Array => {
+            let sink = ElementSink {
+                on_record: &mut on_record,
+                count: 0,
+            };
+            let count = sink
+                .deserialize(&mut deserializer)
+                .map_err(|e| invalid_data(format!("invalid JSON array: {}", e)))?;
+            // Only whitespace may follow the array
+            deserializer
+                .end()
+                .map_err(|e| invalid_data(format!("unexpected data after the array: {}", e)))?;
+            count
+        }
+        JsonLayout::Concatenated => {
+            let mut count = 0;
+            for record in deserializer.into_iter::<Value>() {
+                let record =
+                    record.map_err(|e| invalid_data(format!("invalid record {}: {}", count, e)))?;
+                on_record(record)?;
+                count += 1;
+            }
+            count
+        }
+    };
+    Ok((layout, count))
+}
+
+/// Reads a JSON array file or a stream of concatenated (pretty-printed)
+/// JSON documents as records; the layout is detected from the first byte.
+pub struct JsonDocumentReader {
+    path: PathBuf,
+    filename: String,
+    layout: Option<JsonLayout>,
+    data: Vec<Value>,
+    progress: Option<ProgressCallback>,
+}
+
+impl JsonDocumentReader {
+    pub fn new(path: PathBuf) -> Self {
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            filename,
+            layout: None,
+            data: Vec::new(),
+            progress: None,
+        }
+    }
+
+    /// Layout found by the last [`JsonlReader::load`]
+    pub fn layout(&self) -> Option<JsonLayout> {
+        self.layout
+    }
+}
+
+impl JsonlReader for JsonDocumentReader {
+    type Error = io::Error;
+
+    fn load(&mut self) -> Result<(), Self::Error> {
+        let file = File::open(&self.path)?;
+        let tracker = ProgressTracker::new("read", None, None).with_callback(self.progress.clone());
+        let mut data = Vec::new();
+        let (layout, _) = read_json_documents(file, |record| {
+            data.push(record);
+            tracker.add(1, 0);
+            Ok(())
+        })?;
+        tracker.finish();
+        self.layout = Some(layout);
+        self.data = data;
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&Value> {
+        self.data.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut Value> {
+        self.data.get_mut(index)
+    }
+
+    fn replace(&mut self, index: usize, value: Value) -> Result<(), Self::Error> {
+        if index < self.data.len() {
+            self.data[index] = value;
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Index {} out of bounds", index),
+            ))
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Value> + '_> {
+        Box::new(self.data.iter())
+    }
+
+    fn source_name(&self) -> &str {
+        &self.filename
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), Self::Error> {
+        self.data.push(value);
+        Ok(())
+    }
+
+    fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.progress = callback;
+    }
+}
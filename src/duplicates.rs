@@ -0,0 +1,46 @@
+use num_format::{Locale, ToFormattedString};
+
+/// Rows sharing the same canonical record, or the same primary key value
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateGroup {
+    /// Canonical JSON of the shared record or key value
+    pub key: String,
+    /// Row numbers in file order
+    pub rows: Vec<usize>,
+}
+
+/// Which row of a duplicate group `dedup` keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeepPolicy {
+    #[default]
+    First,
+    Last,
+    /// The row with the most keys present; the earliest such row on ties
+    MostKeys,
+}
+
+impl KeepPolicy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "first" => Some(KeepPolicy::First),
+            "last" => Some(KeepPolicy::Last),
+            "most-keys" => Some(KeepPolicy::MostKeys),
+            _ => None,
+        }
+    }
+}
+
+/// Prints duplicate groups under a heading, with the number of redundant rows.
+pub fn show_duplicate_groups(title: &str, groups: &[DuplicateGroup]) {
+    let redundant: usize = groups.iter().map(|g| g.rows.len() - 1).sum();
+    println!("===============================");
+    println!(
+        "{}: {} groups, {} redundant rows",
+        title,
+        groups.len().to_formatted_string(&Locale::en),
+        redundant.to_formatted_string(&Locale::en)
+    );
+    for group in groups {
+        println!("\trows {:?}: {}", group.rows, group.key);
+    }
+}
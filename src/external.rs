@@ -0,0 +1,312 @@
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+    path::{Path, PathBuf},
+    process,
+    rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Scratch directory under the system temp dir, removed on drop
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new(label: &str) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let path = std::env::temp_dir().join(format!(
+            "jsonl_tools-{}-{}-{}-{}",
+            label,
+            process::id(),
+            nanos,
+            TEMP_DIR_COUNTER.fetch_add(1, AtomicOrdering::Relaxed)
+        ));
+        fs::create_dir_all(&path)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Runs merged at once. With more runs, groups of runs are first merged into
+/// longer runs, so that the number of open files stays bounded.
+pub const MAX_MERGE_FAN_IN: usize = 64;
+
+/// Sorts records that may not fit in memory: records are buffered up to
+/// `run_size`, each full buffer is sorted and spilled to a temp file as a
+/// JSONL run, and [`ExternalSorter::finish`] merges the runs.
+pub struct ExternalSorter<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    run_size: usize,
+    fan_in: usize,
+    compare: F,
+    buffer: Vec<Value>,
+    runs: Vec<PathBuf>,
+    dir: TempDir,
+}
+
+impl<F> ExternalSorter<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    pub fn new(run_size: usize, compare: F) -> io::Result<Self> {
+        Ok(Self {
+            run_size: run_size.max(1),
+            fan_in: MAX_MERGE_FAN_IN,
+            compare,
+            buffer: Vec::new(),
+            runs: Vec::new(),
+            dir: TempDir::new("sort")?,
+        })
+    }
+
+    /// Merges at most `fan_in` runs at once (at least 2) instead of [`MAX_MERGE_FAN_IN`].
+    pub fn with_fan_in(mut self, fan_in: usize) -> Self {
+        self.fan_in = fan_in.max(2);
+        self
+    }
+
+    pub fn push(&mut self, record: Value) -> io::Result<()> {
+        self.buffer.push(record);
+        if self.buffer.len() >= self.run_size {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        // Stable sort, so equal records keep their input order within a run
+        self.buffer.sort_by(&self.compare);
+        let path = self
+            .dir
+            .path()
+            .join(format!("run-{:06}.jsonl", self.runs.len()));
+        write_run(&path, self.buffer.drain(..).map(Ok))?;
+        self.runs.push(path);
+        Ok(())
+    }
+
+    /// Returns the records in sorted order, merging the spilled runs.
+    pub fn finish(mut self) -> io::Result<SortedRecords<F>> {
+        if !self.runs.is_empty() && !self.buffer.is_empty() {
+            self.spill()?;
+        }
+
+        let runs = self.runs.len();
+        let compare = Rc::new(self.compare);
+        let merge = if runs == 0 {
+            // Everything fit in one buffer: no need to touch the disk
+            let mut buffer = self.buffer;
+            buffer.sort_by(|a, b| compare(a, b));
+            Merge::new(vec![RunSource::Memory(buffer.into_iter())], compare)?
+        } else {
+            // Consecutive runs are merged together, so equal records stay in
+            // input order across passes
+            let mut paths = self.runs;
+            let mut pass = 0;
+            while paths.len() > self.fan_in {
+                let mut merged = Vec::new();
+                for group in paths.chunks(self.fan_in) {
+                    let path =
+                        self.dir
+                            .path()
+                            .join(format!("merge-{}-{:06}.jsonl", pass, merged.len()));
+                    write_run(&path, Merge::open(group, Rc::clone(&compare))?)?;
+                    for done in group {
+                        fs::remove_file(done)?;
+                    }
+                    merged.push(path);
+                }
+                paths = merged;
+                pass += 1;
+            }
+            Merge::open(&paths, compare)?
+        };
+
+        Ok(SortedRecords {
+            runs,
+            merge,
+            _dir: self.dir,
+        })
+    }
+}
+
+fn write_run<I>(path: &Path, records: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = io::Result<Value>>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    for record in records {
+        serde_json::to_writer(&mut writer, &record?)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+enum RunSource {
+    Memory(std::vec::IntoIter<Value>),
+    File(Lines<BufReader<File>>),
+}
+
+impl RunSource {
+    fn next_record(&mut self) -> io::Result<Option<Value>> {
+        match self {
+            RunSource::Memory(records) => Ok(records.next()),
+            RunSource::File(lines) => match lines.next() {
+                Some(line) => serde_json::from_str(&line?)
+                    .map(Some)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+                None => Ok(None),
+            },
+        }
+    }
+}
+
+/// The next record of one run, ordered for [`BinaryHeap`] so that the
+/// smallest record pops first and the earliest run wins ties
+struct Head<F> {
+    record: Value,
+    source: usize,
+    compare: Rc<F>,
+}
+
+impl<F> Ord for Head<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.compare)(&other.record, &self.record).then_with(|| other.source.cmp(&self.source))
+    }
+}
+
+impl<F> PartialOrd for Head<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<F> PartialEq for Head<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<F> Eq for Head<F> where F: Fn(&Value, &Value) -> Ordering {}
+
+/// K-way merge of sorted runs
+struct Merge<F> {
+    sources: Vec<RunSource>,
+    heads: BinaryHeap<Head<F>>,
+    compare: Rc<F>,
+}
+
+impl<F> Merge<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    fn new(mut sources: Vec<RunSource>, compare: Rc<F>) -> io::Result<Self> {
+        let mut heads = BinaryHeap::with_capacity(sources.len());
+        for (source, run) in sources.iter_mut().enumerate() {
+            if let Some(record) = run.next_record()? {
+                heads.push(Head {
+                    record,
+                    source,
+                    compare: Rc::clone(&compare),
+                });
+            }
+        }
+        Ok(Self {
+            sources,
+            heads,
+            compare,
+        })
+    }
+
+    fn open(paths: &[PathBuf], compare: Rc<F>) -> io::Result<Self> {
+        let sources = paths
+            .iter()
+            .map(|path| Ok(RunSource::File(BufReader::new(File::open(path)?).lines())))
+            .collect::<io::Result<Vec<_>>>()?;
+        Self::new(sources, compare)
+    }
+}
+
+impl<F> Iterator for Merge<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Head { record, source, .. } = self.heads.pop()?;
+        match self.sources[source].next_record() {
+            Ok(Some(next)) => self.heads.push(Head {
+                record: next,
+                source,
+                compare: Rc::clone(&self.compare),
+            }),
+            Ok(None) => {}
+            Err(e) => return Some(Err(e)),
+        }
+        Some(Ok(record))
+    }
+}
+
+/// Iterator over the merged output of an [`ExternalSorter`]
+pub struct SortedRecords<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    runs: usize,
+    merge: Merge<F>,
+    // Keeps the run files alive until the merge is done
+    _dir: TempDir,
+}
+
+impl<F> SortedRecords<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    /// Number of sorted runs spilled to disk; 0 when everything fit in memory
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+}
+
+impl<F> Iterator for SortedRecords<F>
+where
+    F: Fn(&Value, &Value) -> Ordering,
+{
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.merge.next()
+    }
+}
@@ -0,0 +1,278 @@
+use crate::jsonl::collect_key_paths;
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    ops::ControlFlow,
+    path::PathBuf,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Bytes read from the followed file at once
+const READ_CHUNK: usize = 64 * 1024;
+
+/// Something noticed while following a file
+#[derive(Debug, Clone, PartialEq)]
+pub enum FollowEvent {
+    /// A key path not seen before appeared in the given row
+    NewKey {
+        path: String,
+        row: usize,
+    },
+    /// The path now names a different file (the old one was fully read first)
+    Rotated,
+    /// The file shrank; reading restarts from its beginning
+    Truncated,
+    InvalidLine {
+        row: usize,
+        message: String,
+    },
+    /// Time for the periodic key reports
+    Report,
+}
+
+/// Running key statistics of a followed file
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeyStats {
+    pub rows: usize,
+    /// Rows containing each key path
+    pub key_counts: BTreeMap<String, usize>,
+}
+
+impl KeyStats {
+    /// Counts the key paths of a record, returning the ones never seen before.
+    pub fn add_record(&mut self, record: &Value) -> Vec<String> {
+        let mut keys = HashSet::new();
+        collect_key_paths(record, &mut keys, String::new());
+        self.rows += 1;
+
+        let mut new_keys = Vec::new();
+        for key in keys {
+            let count = self.key_counts.entry(key.clone()).or_insert(0);
+            if *count == 0 {
+                new_keys.push(key);
+            }
+            *count += 1;
+        }
+        new_keys.sort();
+        new_keys
+    }
+
+    pub fn show_report(&self, name: &str) {
+        println!("===============================");
+        println!(
+            "{}: {} rows, {} unique JSON keys",
+            name,
+            self.rows.to_formatted_string(&Locale::en),
+            self.key_counts.len().to_formatted_string(&Locale::en)
+        );
+        let width = self
+            .key_counts
+            .keys()
+            .map(String::len)
+            .max()
+            .unwrap_or(20)
+            .max(20);
+        println!(
+            "{:<width$} {:>12} {:>12}",
+            "Key",
+            "Count",
+            "Missing",
+            width = width
+        );
+        println!("{}", "-".repeat(width + 26));
+
+        let mut keys: Vec<(&String, &usize)> = self.key_counts.iter().collect();
+        keys.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        for (key, count) in keys {
+            println!(
+                "\t{:<width$} {:>12} {:>12}",
+                key,
+                count.to_formatted_string(&Locale::en),
+                (self.rows - count).to_formatted_string(&Locale::en),
+                width = width
+            );
+        }
+    }
+}
+
+/// `tail -f` for JSONL: reads what is appended to a file, following it across
+/// rotation (the path is recreated) and truncation. The content present when
+/// following starts is the baseline: it is counted, but raises no new-key events.
+pub struct Follower {
+    path: PathBuf,
+    pub poll_interval: Duration,
+    pub report_interval: Duration,
+    file: Option<File>,
+    identity: Option<(u64, u64)>,
+    position: u64,
+    /// Bytes of a line whose newline has not been written yet
+    partial: Vec<u8>,
+    stats: KeyStats,
+}
+
+/// Device and inode of a file, to notice when its path is recreated
+#[cfg(unix)]
+fn file_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+impl Follower {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            poll_interval: Duration::from_millis(500),
+            report_interval: Duration::from_secs(10),
+            file: None,
+            identity: None,
+            position: 0,
+            partial: Vec::new(),
+            stats: KeyStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> &KeyStats {
+        &self.stats
+    }
+
+    fn open(&mut self) -> io::Result<()> {
+        let file = File::open(&self.path)?;
+        self.identity = file_identity(&file.metadata()?);
+        self.file = Some(file);
+        self.position = 0;
+        self.partial.clear();
+        Ok(())
+    }
+
+    /// Reads the current content of the file as the baseline.
+    pub fn start(&mut self) -> io::Result<()> {
+        self.open()?;
+        let mut events = Vec::new();
+        self.read_appended(&mut events, false)?;
+        Ok(())
+    }
+
+    /// Reads whatever was appended since the last poll and returns what happened.
+    pub fn poll(&mut self) -> io::Result<Vec<FollowEvent>> {
+        let mut events = Vec::new();
+        if self.file.is_none() {
+            match self.open() {
+                Ok(()) => {}
+                // Rotated away and not recreated yet
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(events),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match fs::metadata(&self.path) {
+            Ok(metadata) => {
+                let identity = file_identity(&metadata);
+                if identity.is_some() && identity != self.identity {
+                    // Finish the old file before switching to the new one
+                    self.read_appended(&mut events, true)?;
+                    self.open()?;
+                    events.push(FollowEvent::Rotated);
+                } else if metadata.len() < self.position {
+                    self.position = 0;
+                    self.partial.clear();
+                    events.push(FollowEvent::Truncated);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+
+        self.read_appended(&mut events, true)?;
+        Ok(events)
+    }
+
+    /// Reads to the end of the file in chunks, processing the complete lines
+    /// of each chunk before reading the next, so a large backlog is never
+    /// held in memory at once.
+    fn read_appended(&mut self, events: &mut Vec<FollowEvent>, alert: bool) -> io::Result<()> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        file.seek(SeekFrom::Start(self.position))?;
+        let mut chunk = vec![0u8; READ_CHUNK];
+        loop {
+            let read = match file.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            self.position += read as u64;
+            // The pending partial line has no newline, so only the new bytes can end it
+            let search_from = self.partial.len();
+            self.partial.extend_from_slice(&chunk[..read]);
+            if let Some(last_newline) = self.partial[search_from..]
+                .iter()
+                .rposition(|&b| b == b'\n')
+            {
+                let complete: Vec<u8> = self.partial.drain(..=search_from + last_newline).collect();
+                record_lines(&mut self.stats, &complete, events, alert);
+            }
+        }
+    }
+
+    /// Reads the baseline, then polls until `handle` breaks, adding a
+    /// [`FollowEvent::Report`] every `report_interval`.
+    pub fn follow<F>(&mut self, mut handle: F) -> io::Result<()>
+    where
+        F: FnMut(&FollowEvent, &KeyStats) -> ControlFlow<()>,
+    {
+        self.start()?;
+        if handle(&FollowEvent::Report, &self.stats).is_break() {
+            return Ok(());
+        }
+
+        let mut last_report = Instant::now();
+        loop {
+            thread::sleep(self.poll_interval);
+            let mut events = self.poll()?;
+            if last_report.elapsed() >= self.report_interval {
+                events.push(FollowEvent::Report);
+                last_report = Instant::now();
+            }
+            for event in &events {
+                if handle(event, &self.stats).is_break() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+}
+
+/// Adds the records of complete lines to `stats`; with `alert`, new key
+/// paths raise events. Invalid lines always do.
+fn record_lines(stats: &mut KeyStats, complete: &[u8], events: &mut Vec<FollowEvent>, alert: bool) {
+    for line in complete.split(|&b| b == b'\n') {
+        if line.iter().all(u8::is_ascii_whitespace) {
+            continue;
+        }
+        let row = stats.rows;
+        match serde_json::from_slice::<Value>(line) {
+            Ok(record) => {
+                for path in stats.add_record(&record) {
+                    if alert {
+                        events.push(FollowEvent::NewKey { path, row });
+                    }
+                }
+            }
+            Err(e) => events.push(FollowEvent::InvalidLine {
+                row,
+                message: e.to_string(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use crate::convert::TableFormat;
+use crate::jsonl::JsonlReader;
+use crate::path::{KeyPath, Segment};
+use crate::timestamps::parse_iso;
+use num_format::{Locale, ToFormattedString};
+use serde_json::{Map, Value};
+use std::{fmt, fs, io, path::PathBuf};
+
+/// Type inferred for a CSV column from all of its non-empty cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Only empty cells (and `null` ones with [`CsvOptions::literal_null`])
+    Empty,
+    Bool,
+    Int,
+    Float,
+    /// ISO 8601 / RFC 3339 dates and times, kept as strings
+    Timestamp,
+    /// JSON arrays or objects, as written by `convert`
+    Json,
+    String,
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColumnType::Empty => "empty",
+            ColumnType::Bool => "bool",
+            ColumnType::Int => "int",
+            ColumnType::Float => "float",
+            ColumnType::Timestamp => "timestamp",
+            ColumnType::Json => "json",
+            ColumnType::String => "string",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ColumnType {
+    /// Type of a single cell; `None` for empty cells, and for `null` ones
+    /// when they are read as JSON null
+    fn of_cell(cell: &str, literal_null: bool) -> Option<Self> {
+        if cell.is_empty() || (literal_null && cell == "null") {
+            return None;
+        }
+        let kind = if cell.eq_ignore_ascii_case("true") || cell.eq_ignore_ascii_case("false") {
+            ColumnType::Bool
+        } else if is_number(cell) && is_integer(cell) {
+            // Integers beyond 64 bits would lose digits as floats, so they stay strings
+            if parse_integer(cell).is_some() {
+                ColumnType::Int
+            } else {
+                ColumnType::String
+            }
+        } else if is_number(cell) && cell.parse::<f64>().is_ok_and(f64::is_finite) {
+            ColumnType::Float
+        } else if is_timestamp(cell) {
+            ColumnType::Timestamp
+        } else if (cell.starts_with('[') || cell.starts_with('{'))
+            && serde_json::from_str::<Value>(cell).is_ok()
+        {
+            ColumnType::Json
+        } else {
+            ColumnType::String
+        };
+        Some(kind)
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (ColumnType::Empty, x) | (x, ColumnType::Empty) => x,
+            (ColumnType::Int, ColumnType::Float) | (ColumnType::Float, ColumnType::Int) => {
+                ColumnType::Float
+            }
+            _ => ColumnType::String,
+        }
+    }
+
+    fn convert(&self, cell: &str, literal_null: bool) -> Value {
+        if literal_null && cell == "null" {
+            return Value::Null;
+        }
+        let parsed = match self {
+            ColumnType::Bool => Some(Value::Bool(cell.eq_ignore_ascii_case("true"))),
+            ColumnType::Int => parse_integer(cell),
+            ColumnType::Float => cell.parse::<f64>().ok().map(Value::from),
+            ColumnType::Json => serde_json::from_str(cell).ok(),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| Value::String(cell.to_string()))
+    }
+}
+
+/// Plain decimal notation; numbers with leading zeros (zip codes, ids) stay strings
+fn is_number(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell);
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    !leading_zero
+        && digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '-' | '+'))
+}
+
+fn is_integer(cell: &str) -> bool {
+    let digits = cell.strip_prefix(['-', '+']).unwrap_or(cell);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// An integer that fits `i64` or, if positive, `u64`
+fn parse_integer(cell: &str) -> Option<Value> {
+    cell.parse::<i64>()
+        .map(Value::from)
+        .or_else(|_| cell.parse::<u64>().map(Value::from))
+        .ok()
+}
+
+fn is_timestamp(cell: &str) -> bool {
+    parse_iso(cell).is_some()
+}
+
+/// How a CSV or TSV file is turned into records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CsvOptions {
+    pub format: TableFormat,
+    /// Rebuild nested objects from dotted headers (`address.city`)
+    pub nested: bool,
+    /// Store empty cells as `null` instead of leaving the key out
+    pub empty_as_null: bool,
+    /// Read cells holding the text `null` (as `convert` writes JSON null) as
+    /// null instead of as the string `"null"`
+    pub literal_null: bool,
+}
+
+impl CsvOptions {
+    pub fn new(format: TableFormat) -> Self {
+        Self {
+            format,
+            nested: false,
+            empty_as_null: false,
+            literal_null: false,
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Splits CSV text into rows of cells. Quoted cells may contain delimiters,
+/// doubled quotes and line breaks.
+fn parse_csv(text: &str) -> io::Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    cell.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if cell.is_empty() => in_quotes = true,
+            ',' if !in_quotes => row.push(std::mem::take(&mut cell)),
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+                line += 1;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                cell.push(c);
+            }
+        }
+    }
+    if in_quotes {
+        return Err(invalid_data(format!(
+            "unterminated quoted cell at line {}",
+            line
+        )));
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Splits TSV text into rows of cells, undoing the `\t`, `\n`, `\r` and `\\` escapes.
+fn parse_tsv(text: &str) -> Vec<Vec<String>> {
+    text.lines()
+        .map(|line| {
+            line.split('\t')
+                .map(|cell| {
+                    let mut out = String::with_capacity(cell.len());
+                    let mut chars = cell.chars();
+                    while let Some(c) = chars.next() {
+                        if c != '\\' {
+                            out.push(c);
+                            continue;
+                        }
+                        match chars.next() {
+                            Some('t') => out.push('\t'),
+                            Some('n') => out.push('\n'),
+                            Some('r') => out.push('\r'),
+                            Some(other) => out.push(other),
+                            None => out.push('\\'),
+                        }
+                    }
+                    out
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Reads a CSV or TSV file with a header row as records, inferring a type
+/// per column, so the key reports and commands work on it like on JSONL.
+pub struct CsvJsonlReader {
+    path: PathBuf,
+    filename: String,
+    options: CsvOptions,
+    columns: Vec<(String, ColumnType)>,
+    data: Vec<Value>,
+}
+
+impl CsvJsonlReader {
+    pub fn new(path: PathBuf, options: CsvOptions) -> Self {
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            filename,
+            options,
+            columns: Vec::new(),
+            data: Vec::new(),
+        }
+    }
+
+    /// Header names with their inferred types, in file order
+    pub fn columns(&self) -> &[(String, ColumnType)] {
+        &self.columns
+    }
+
+    pub fn show_schema_report(&self) {
+        println!("===============================");
+        println!(
+            "Inferred column types of {} ({} rows)",
+            self.filename,
+            self.data.len().to_formatted_string(&Locale::en)
+        );
+        for (name, kind) in &self.columns {
+            println!("\t{:<30} {}", name, kind);
+        }
+    }
+
+    fn build_record(&self, cells: &[String], paths: &[Option<KeyPath>]) -> Value {
+        let mut record = Value::Object(Map::new());
+        for (i, (name, kind)) in self.columns.iter().enumerate() {
+            let cell = cells.get(i).map_or("", String::as_str);
+            let value = if cell.is_empty() {
+                if !self.options.empty_as_null {
+                    continue;
+                }
+                Value::Null
+            } else {
+                kind.convert(cell, self.options.literal_null)
+            };
+
+            // A header clashing with another one (`a` and `a.b`) stays a flat key
+            let inserted = match &paths[i] {
+                Some(path) => path.insert(&mut record, value.clone()),
+                None => false,
+            };
+            if !inserted && let Value::Object(map) = &mut record {
+                map.insert(name.clone(), value);
+            }
+        }
+        record
+    }
+}
+
+impl JsonlReader for CsvJsonlReader {
+    type Error = io::Error;
+
+    fn load(&mut self) -> Result<(), Self::Error> {
+        let text = fs::read_to_string(&self.path)?;
+        let mut rows = match self.options.format {
+            TableFormat::Csv => parse_csv(&text)?,
+            TableFormat::Tsv => parse_tsv(&text),
+        }
+        .into_iter()
+        .filter(|row| !(row.len() == 1 && row[0].trim().is_empty()));
+
+        let header = rows
+            .next()
+            .ok_or_else(|| invalid_data(format!("{} has no header row", self.filename)))?;
+        let rows: Vec<Vec<String>> = rows.collect();
+
+        let mut types = vec![ColumnType::Empty; header.len()];
+        for (row_number, row) in rows.iter().enumerate() {
+            if row.len() > header.len() {
+                return Err(invalid_data(format!(
+                    "row {} has {} cells but the header has {}",
+                    row_number,
+                    row.len(),
+                    header.len()
+                )));
+            }
+            for (kind, cell) in types.iter_mut().zip(row) {
+                if let Some(cell_type) = ColumnType::of_cell(cell, self.options.literal_null) {
+                    *kind = kind.merge(cell_type);
+                }
+            }
+        }
+        self.columns = header.into_iter().zip(types).collect();
+
+        let paths: Vec<Option<KeyPath>> = self
+            .columns
+            .iter()
+            .map(|(name, _)| {
+                (self.options.nested && name.contains('.')).then(|| {
+                    KeyPath::from_segments(
+                        name.split('.')
+                            .map(|part| Segment::Key(part.to_string()))
+                            .collect(),
+                    )
+                })
+            })
+            .collect();
+        self.data = rows
+            .iter()
+            .map(|row| self.build_record(row, &paths))
+            .collect();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn get(&self, index: usize) -> Option<&Value> {
+        self.data.get(index)
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut Value> {
+        self.data.get_mut(index)
+    }
+
+    fn replace(&mut self, index: usize, value: Value) -> Result<(), Self::Error> {
+        if index < self.data.len() {
+            self.data[index] = value;
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Index {} out of bounds", index),
+            ))
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Value> + '_> {
+        Box::new(self.data.iter())
+    }
+
+    fn source_name(&self) -> &str {
+        &self.filename
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), Self::Error> {
+        self.data.push(value);
+        Ok(())
+    }
+}
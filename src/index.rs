@@ -0,0 +1,349 @@
+use memmap2::Mmap;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+const MAGIC: &[u8; 8] = b"JSONLIDX";
+const VERSION: u64 = 1;
+/// Magic, version, file size, modification time and record count
+const HEADER_LEN: usize = 40;
+/// Block size used when scanning a file backwards for `tail`
+const TAIL_BLOCK: u64 = 64 * 1024;
+
+/// Byte offsets of the records (non-blank lines) of a JSONL file.
+///
+/// Saved next to the file as `<file>.idx`, together with the file's size and
+/// modification time; a sidecar whose stamp no longer matches is ignored.
+/// A loaded sidecar is memory-mapped, so a lookup reads one offset from it
+/// instead of the whole table being read up front.
+#[derive(Debug)]
+pub struct LineIndex {
+    offsets: Offsets,
+    file_len: u64,
+    modified: u64,
+}
+
+#[derive(Debug)]
+enum Offsets {
+    Built(Vec<u64>),
+    /// The whole sidecar, offsets starting at [`HEADER_LEN`]
+    Mapped {
+        map: Mmap,
+        count: usize,
+    },
+}
+
+impl PartialEq for LineIndex {
+    fn eq(&self, other: &Self) -> bool {
+        self.file_len == other.file_len
+            && self.modified == other.modified
+            && self.len() == other.len()
+            && (0..self.len()).all(|row| self.offset(row) == other.offset(row))
+    }
+}
+
+/// Which rows to read: the first or last N, or a half-open range of row numbers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowSelection {
+    Head(usize),
+    Tail(usize),
+    Range { start: usize, end: Option<usize> },
+}
+
+impl RowSelection {
+    /// Parses `A..B`, `A..` or `..B` (row numbers from 0, end exclusive).
+    pub fn parse_range(spec: &str) -> Option<Self> {
+        let (start, end) = spec.split_once("..")?;
+        let start = match start.trim() {
+            "" => 0,
+            n => n.parse().ok()?,
+        };
+        let end = match end.trim() {
+            "" => None,
+            n => Some(n.parse().ok()?),
+        };
+        Some(RowSelection::Range { start, end })
+    }
+
+    /// Row numbers selected out of `len` rows.
+    pub fn resolve(&self, len: usize) -> Range<usize> {
+        match *self {
+            RowSelection::Head(n) => 0..n.min(len),
+            RowSelection::Tail(n) => len.saturating_sub(n)..len,
+            RowSelection::Range { start, end } => {
+                let end = end.unwrap_or(len).min(len);
+                start.min(end)..end
+            }
+        }
+    }
+}
+
+/// Size and modification time (ns since the epoch) identifying a file version
+fn file_stamp(path: &Path) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default();
+    Ok((metadata.len(), modified))
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter().all(u8::is_ascii_whitespace)
+}
+
+/// The little-endian `u64` at `bytes[at..at + 8]`
+fn u64_at(bytes: &[u8], at: usize) -> Option<u64> {
+    let bytes = bytes.get(at..at.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().ok()?))
+}
+
+impl LineIndex {
+    /// Scans the whole file once, recording where each record starts.
+    pub fn build(path: &Path) -> io::Result<Self> {
+        let (file_len, modified) = file_stamp(path)?;
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut offsets = Vec::new();
+        let mut offset = 0u64;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            if !is_blank(&line) {
+                offsets.push(offset);
+            }
+            offset += read as u64;
+        }
+        Ok(Self {
+            offsets: Offsets::Built(offsets),
+            file_len,
+            modified,
+        })
+    }
+
+    pub fn sidecar_path(path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".idx");
+        PathBuf::from(name)
+    }
+
+    /// Loads the sidecar index of `path`, or `None` when there is none or it is stale.
+    pub fn load(path: &Path) -> io::Result<Option<Self>> {
+        let sidecar = Self::sidecar_path(path);
+        if !sidecar.exists() {
+            return Ok(None);
+        }
+        let file = File::open(&sidecar)?;
+        if file.metadata()?.len() < HEADER_LEN as u64 {
+            return Ok(None);
+        }
+        // SAFETY: the map is read-only, and `save` replaces sidecars instead of
+        // rewriting them in place
+        let map = unsafe { Mmap::map(&file)? };
+        let header = |i: usize| u64_at(&map, 8 + 8 * i).unwrap_or_default();
+        if &map[..8] != MAGIC || header(0) != VERSION {
+            return Ok(None);
+        }
+        let (file_len, modified, count) = (header(1), header(2), header(3) as usize);
+        let table_len = count.checked_mul(8).and_then(|n| n.checked_add(HEADER_LEN));
+        if file_stamp(path)? != (file_len, modified) || table_len != Some(map.len()) {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            offsets: Offsets::Mapped { map, count },
+            file_len,
+            modified,
+        }))
+    }
+
+    /// Writes the sidecar to a temp file renamed over the old one, so that
+    /// readers still mapping the old sidecar are not affected.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let sidecar = Self::sidecar_path(path);
+        let mut temp = sidecar.clone().into_os_string();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        writer.write_all(MAGIC)?;
+        for n in [VERSION, self.file_len, self.modified, self.len() as u64] {
+            writer.write_all(&n.to_le_bytes())?;
+        }
+        for row in 0..self.len() {
+            writer.write_all(&self.offset(row).unwrap_or_default().to_le_bytes())?;
+        }
+        writer.flush()?;
+        drop(writer);
+        fs::rename(&temp, &sidecar)
+    }
+
+    /// Loads a valid sidecar index, or builds and saves a new one.
+    pub fn load_or_build(path: &Path) -> io::Result<Self> {
+        if let Some(index) = Self::load(path)? {
+            return Ok(index);
+        }
+        let index = Self::build(path)?;
+        index.save(path)?;
+        Ok(index)
+    }
+
+    /// Number of records in the file
+    pub fn len(&self) -> usize {
+        match &self.offsets {
+            Offsets::Built(offsets) => offsets.len(),
+            Offsets::Mapped { count, .. } => *count,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Byte offset where record `row` starts
+    pub fn offset(&self, row: usize) -> Option<u64> {
+        match &self.offsets {
+            Offsets::Built(offsets) => offsets.get(row).copied(),
+            Offsets::Mapped { map, count } if row < *count => u64_at(map, HEADER_LEN + 8 * row),
+            Offsets::Mapped { .. } => None,
+        }
+    }
+}
+
+/// Writes the selected rows of a JSONL file, as they appear in the file, one
+/// per line. Only the needed part of the file is read: with an index it seeks
+/// straight to the first row, without one `head` and ranges stop reading at
+/// the last row and `tail` scans backwards from the end.
+pub fn write_rows<W: Write>(
+    path: &Path,
+    selection: &RowSelection,
+    index: Option<&LineIndex>,
+    mut writer: W,
+) -> io::Result<usize> {
+    let lines = match (index, selection) {
+        (Some(index), _) => {
+            let range = selection.resolve(index.len());
+            let mut reader = BufReader::new(File::open(path)?);
+            if let Some(offset) = index.offset(range.start) {
+                reader.seek(SeekFrom::Start(offset))?;
+            }
+            copy_lines(reader, range.len(), &mut writer)?
+        }
+        (None, RowSelection::Tail(n)) => {
+            let tail = tail_lines(path, *n)?;
+            for line in &tail {
+                writer.write_all(line)?;
+                writer.write_all(b"\n")?;
+            }
+            tail.len()
+        }
+        (None, _) => {
+            // Head and ranges only need the rows up to the end of the selection
+            let range = selection.resolve(usize::MAX);
+            let mut reader = BufReader::new(File::open(path)?);
+            skip_lines(&mut reader, range.start)?;
+            copy_lines(reader, range.len(), &mut writer)?
+        }
+    };
+    writer.flush()?;
+    Ok(lines)
+}
+
+fn skip_lines(reader: &mut impl BufRead, count: usize) -> io::Result<()> {
+    let mut skipped = 0;
+    let mut line = Vec::new();
+    while skipped < count {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if !is_blank(&line) {
+            skipped += 1;
+        }
+    }
+    Ok(())
+}
+
+fn copy_lines(
+    mut reader: impl BufRead,
+    count: usize,
+    writer: &mut impl Write,
+) -> io::Result<usize> {
+    let mut copied = 0;
+    let mut line = Vec::new();
+    while copied < count {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        if is_blank(&line) {
+            continue;
+        }
+        writer.write_all(trim_newline(&line))?;
+        writer.write_all(b"\n")?;
+        copied += 1;
+    }
+    Ok(copied)
+}
+
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+/// The last `n` records of a file, read in blocks from the end. Each block is
+/// scanned once; a line spanning blocks is kept in pieces until its start is found.
+fn tail_lines(path: &Path, n: usize) -> io::Result<Vec<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let mut position = file.metadata()?.len();
+    // Records found so far, last first
+    let mut lines: Vec<Vec<u8>> = Vec::new();
+    // Pieces of the line the blocks read so far start in, last piece first
+    let mut partial: Vec<Vec<u8>> = Vec::new();
+    let keep = |lines: &mut Vec<Vec<u8>>, line: Vec<u8>| {
+        if !is_blank(&line) {
+            lines.push(trim_newline(&line).to_vec());
+        }
+    };
+    let join = |first: &[u8], partial: &mut Vec<Vec<u8>>| {
+        let mut line = first.to_vec();
+        for piece in partial.drain(..).rev() {
+            line.extend_from_slice(&piece);
+        }
+        line
+    };
+
+    while lines.len() < n && position > 0 {
+        let block_len = TAIL_BLOCK.min(position);
+        position -= block_len;
+        file.seek(SeekFrom::Start(position))?;
+        let mut block = vec![0u8; block_len as usize];
+        file.read_exact(&mut block)?;
+
+        let mut end = block.len();
+        while lines.len() < n
+            && let Some(newline) = block[..end].iter().rposition(|&b| b == b'\n')
+        {
+            let line = join(&block[newline + 1..end], &mut partial);
+            keep(&mut lines, line);
+            end = newline;
+        }
+        block.truncate(end);
+        partial.push(block);
+    }
+    // The start of the file ends the first line
+    if lines.len() < n && position == 0 {
+        let line = join(&[], &mut partial);
+        keep(&mut lines, line);
+    }
+
+    lines.reverse();
+    Ok(lines)
+}
@@ -0,0 +1,487 @@
+use crate::external::ExternalSorter;
+use crate::path::{KeyPath, Segment};
+use crate::value::{compare_values, display_value, number_value};
+use num_format::{Locale, ToFormattedString};
+use serde_json::{Map, Value};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+/// Which unmatched records a join keeps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinKind {
+    /// Only records with a match on both sides
+    #[default]
+    Inner,
+    /// Every left record, merged with its matches if any
+    Left,
+    /// Every record from both sides
+    FullOuter,
+}
+
+impl JoinKind {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "inner" => Some(JoinKind::Inner),
+            "left" => Some(JoinKind::Left),
+            "full" | "outer" | "full-outer" => Some(JoinKind::FullOuter),
+            _ => None,
+        }
+    }
+}
+
+/// How to merge a top-level key present in both the left and right record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep both values, renamed with these prefixes (e.g. `left_name`, `right_name`)
+    Prefix {
+        left: String,
+        right: String,
+    },
+    LeftWins,
+    RightWins,
+}
+
+impl Default for ConflictPolicy {
+    fn default() -> Self {
+        ConflictPolicy::Prefix {
+            left: "left_".to_string(),
+            right: "right_".to_string(),
+        }
+    }
+}
+
+impl ConflictPolicy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "prefix" => Some(ConflictPolicy::default()),
+            "left-wins" => Some(ConflictPolicy::LeftWins),
+            "right-wins" => Some(ConflictPolicy::RightWins),
+            _ => None,
+        }
+    }
+}
+
+/// How matching records are found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JoinStrategy {
+    /// Index the right side in a hash table held in memory and stream the
+    /// left side; output follows the left input order
+    #[default]
+    Hash,
+    /// Stream both sides through temp files holding at most `run_size`
+    /// records in memory at once, then merge; output is ordered by key
+    SortMerge { run_size: usize },
+}
+
+/// Join of two JSONL sources on key paths
+#[derive(Debug, Clone)]
+pub struct Join {
+    pub left_key: KeyPath,
+    pub right_key: KeyPath,
+    pub kind: JoinKind,
+    pub conflicts: ConflictPolicy,
+    pub strategy: JoinStrategy,
+}
+
+/// Outcome of a join, including the key values found on only one side
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JoinSummary {
+    pub rows_written: usize,
+    pub matched_pairs: usize,
+    pub unmatched_left_keys: Vec<Value>,
+    pub unmatched_right_keys: Vec<Value>,
+    /// Records with a missing or null join key, which never match
+    pub left_without_key: usize,
+    pub right_without_key: usize,
+}
+
+/// The join key of a record; missing and null keys never match.
+fn key_of<'a>(path: &KeyPath, record: &'a Value) -> Option<&'a Value> {
+    path.get(record).filter(|v| !v.is_null())
+}
+
+/// Text identifying a join key. Both strategies match keys with equal text:
+/// numbers by value (`1` matches `1.0`), everything else by its JSON text.
+fn key_text(key: &Value) -> String {
+    match key {
+        Value::Number(n) if n.is_f64() => n
+            .as_f64()
+            .map_or(n.to_string(), |f| number_value(f).to_string()),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(key_text).collect();
+            format!("[{}]", items.join(","))
+        }
+        Value::Object(map) => {
+            let entries: Vec<String> = map
+                .iter()
+                .map(|(k, v)| format!("{}:{}", Value::from(k.as_str()), key_text(v)))
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Collects output records and the unmatched key sets while a join runs
+struct JoinOutput<'a, W: Write> {
+    join: &'a Join,
+    writer: W,
+    summary: JoinSummary,
+    unmatched_left: BTreeMap<String, Value>,
+    unmatched_right: BTreeMap<String, Value>,
+}
+
+impl<W: Write> JoinOutput<'_, W> {
+    fn emit(&mut self, record: Value) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &record)?;
+        self.writer.write_all(b"\n")?;
+        self.summary.rows_written += 1;
+        Ok(())
+    }
+
+    fn matched(&mut self, left: &Value, right: &Value) -> io::Result<()> {
+        self.summary.matched_pairs += 1;
+        let merged = self.join.merge(Some(left), Some(right));
+        self.emit(merged)
+    }
+
+    fn left_only(&mut self, record: &Value) -> io::Result<()> {
+        match key_of(&self.join.left_key, record) {
+            Some(key) => {
+                self.unmatched_left.insert(key_text(key), key.clone());
+            }
+            None => self.summary.left_without_key += 1,
+        }
+        if self.join.kind != JoinKind::Inner {
+            let merged = self.join.merge(Some(record), None);
+            self.emit(merged)?;
+        }
+        Ok(())
+    }
+
+    fn right_only(&mut self, record: &Value) -> io::Result<()> {
+        match key_of(&self.join.right_key, record) {
+            Some(key) => {
+                self.unmatched_right.insert(key_text(key), key.clone());
+            }
+            None => self.summary.right_without_key += 1,
+        }
+        if self.join.kind == JoinKind::FullOuter {
+            let merged = self.join.merge(None, Some(record));
+            self.emit(merged)?;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> io::Result<JoinSummary> {
+        self.writer.flush()?;
+        let sorted = |keys: BTreeMap<String, Value>| {
+            let mut keys: Vec<Value> = keys.into_values().collect();
+            keys.sort_by(compare_values);
+            keys
+        };
+        self.summary.unmatched_left_keys = sorted(self.unmatched_left);
+        self.summary.unmatched_right_keys = sorted(self.unmatched_right);
+        Ok(self.summary)
+    }
+}
+
+impl Join {
+    /// Inner hash join with prefixed conflicts; adjust the public fields as needed.
+    pub fn new(left_key: KeyPath, right_key: KeyPath) -> Self {
+        Self {
+            left_key,
+            right_key,
+            kind: JoinKind::default(),
+            conflicts: ConflictPolicy::default(),
+            strategy: JoinStrategy::default(),
+        }
+    }
+
+    /// Joins the `left` records with the `right` records, writing merged
+    /// records to `writer` as JSONL.
+    pub fn run<L, R, W>(&self, left: L, right: R, writer: W) -> io::Result<JoinSummary>
+    where
+        L: IntoIterator<Item = io::Result<Value>>,
+        R: IntoIterator<Item = io::Result<Value>>,
+        W: Write,
+    {
+        let mut output = JoinOutput {
+            join: self,
+            writer,
+            summary: JoinSummary::default(),
+            unmatched_left: BTreeMap::new(),
+            unmatched_right: BTreeMap::new(),
+        };
+
+        match self.strategy {
+            JoinStrategy::Hash => self.hash_join(left, right, &mut output)?,
+            JoinStrategy::SortMerge { run_size } => {
+                self.sort_merge_join(left, right, run_size, &mut output)?
+            }
+        }
+
+        output.finish()
+    }
+
+    fn hash_join<L, R, W>(&self, left: L, right: R, output: &mut JoinOutput<W>) -> io::Result<()>
+    where
+        L: IntoIterator<Item = io::Result<Value>>,
+        R: IntoIterator<Item = io::Result<Value>>,
+        W: Write,
+    {
+        let mut rights = Vec::new();
+        let mut index: HashMap<String, Vec<usize>> = HashMap::new();
+        for record in right {
+            let record = record?;
+            if let Some(key) = key_of(&self.right_key, &record) {
+                index.entry(key_text(key)).or_default().push(rights.len());
+            }
+            rights.push(record);
+        }
+
+        let mut right_matched = vec![false; rights.len()];
+        for record in left {
+            let record = record?;
+            let matches = key_of(&self.left_key, &record).and_then(|key| index.get(&key_text(key)));
+            match matches {
+                Some(rows) => {
+                    for &row in rows {
+                        right_matched[row] = true;
+                        output.matched(&record, &rights[row])?;
+                    }
+                }
+                None => output.left_only(&record)?,
+            }
+        }
+
+        for (record, matched) in rights.iter().zip(right_matched) {
+            if !matched {
+                output.right_only(record)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn sort_merge_join<L, R, W>(
+        &self,
+        left: L,
+        right: R,
+        run_size: usize,
+        output: &mut JoinOutput<W>,
+    ) -> io::Result<()>
+    where
+        L: IntoIterator<Item = io::Result<Value>>,
+        R: IntoIterator<Item = io::Result<Value>>,
+        W: Write,
+    {
+        let left_sorted = sort_by_key(left, &self.left_key, run_size)?;
+        let right_sorted = sort_by_key(right, &self.right_key, run_size)?;
+        let mut left_groups = KeyGroups::new(left_sorted, &self.left_key);
+        let mut right_groups = KeyGroups::new(right_sorted, &self.right_key);
+
+        let mut left_group = left_groups.next_group()?;
+        let mut right_group = right_groups.next_group()?;
+
+        loop {
+            let ordering = match (&left_group, &right_group) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((None, _)), _) => Ordering::Less,
+                (_, Some((None, _))) => Ordering::Greater,
+                (Some((Some(l), _)), Some((Some(r), _))) => compare_keys(Some(l), Some(r)),
+            };
+
+            match ordering {
+                Ordering::Less => {
+                    if let Some((_, records)) = left_group.take() {
+                        for record in &records {
+                            output.left_only(record)?;
+                        }
+                    }
+                    left_group = left_groups.next_group()?;
+                }
+                Ordering::Greater => {
+                    if let Some((_, records)) = right_group.take() {
+                        for record in &records {
+                            output.right_only(record)?;
+                        }
+                    }
+                    right_group = right_groups.next_group()?;
+                }
+                Ordering::Equal => {
+                    if let (Some((_, lefts)), Some((_, rights))) =
+                        (left_group.take(), right_group.take())
+                    {
+                        for l in &lefts {
+                            for r in &rights {
+                                output.matched(l, r)?;
+                            }
+                        }
+                    }
+                    left_group = left_groups.next_group()?;
+                    right_group = right_groups.next_group()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Merges the top-level keys of a left and right record. Keys present on
+    /// both sides are resolved by the conflict policy, except a join key that
+    /// both sides name the same way.
+    pub fn merge(&self, left: Option<&Value>, right: Option<&Value>) -> Value {
+        let empty = Map::new();
+        let left = left.and_then(Value::as_object).unwrap_or(&empty);
+        let right = right.and_then(Value::as_object).unwrap_or(&empty);
+
+        let shared_key = match (self.left_key.segments(), self.right_key.segments()) {
+            ([Segment::Key(l)], [Segment::Key(r)]) if l == r => Some(l.as_str()),
+            _ => None,
+        };
+
+        let mut merged = left.clone();
+        for (key, right_value) in right {
+            let Some(left_value) = left.get(key) else {
+                merged.insert(key.clone(), right_value.clone());
+                continue;
+            };
+            if Some(key.as_str()) == shared_key {
+                continue;
+            }
+            match &self.conflicts {
+                ConflictPolicy::LeftWins => {}
+                ConflictPolicy::RightWins => {
+                    merged.insert(key.clone(), right_value.clone());
+                }
+                ConflictPolicy::Prefix {
+                    left: left_prefix,
+                    right: right_prefix,
+                } => {
+                    merged.remove(key);
+                    merged.insert(format!("{}{}", left_prefix, key), left_value.clone());
+                    merged.insert(format!("{}{}", right_prefix, key), right_value.clone());
+                }
+            }
+        }
+        Value::Object(merged)
+    }
+}
+
+/// Sorts records by join key through an [`ExternalSorter`].
+fn sort_by_key(
+    records: impl IntoIterator<Item = io::Result<Value>>,
+    path: &KeyPath,
+    run_size: usize,
+) -> io::Result<impl Iterator<Item = io::Result<Value>>> {
+    let path = path.clone();
+    let mut sorter = ExternalSorter::new(run_size, move |a: &Value, b: &Value| {
+        compare_keys(key_of(&path, a), key_of(&path, b))
+    })?;
+    for record in records {
+        sorter.push(record?)?;
+    }
+    sorter.finish()
+}
+
+/// Orders join keys with missing keys first, so the merge can skip them.
+/// Keys are equal exactly when their [`key_text`] is, as in the hash join.
+fn compare_keys(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => compare_values(a, b).then_with(|| key_text(a).cmp(&key_text(b))),
+        (a, b) => a.is_some().cmp(&b.is_some()),
+    }
+}
+
+/// Reads consecutive records sharing a join key from a key-sorted stream
+struct KeyGroups<'a, I: Iterator<Item = io::Result<Value>>> {
+    records: I,
+    path: &'a KeyPath,
+    pending: Option<Value>,
+}
+
+impl<'a, I: Iterator<Item = io::Result<Value>>> KeyGroups<'a, I> {
+    fn new(records: I, path: &'a KeyPath) -> Self {
+        Self {
+            records,
+            path,
+            pending: None,
+        }
+    }
+
+    /// Next group of records with equal keys. Records without a key are
+    /// returned one at a time with a `None` key.
+    fn next_group(&mut self) -> io::Result<Option<(Option<Value>, Vec<Value>)>> {
+        let first = match self.pending.take() {
+            Some(record) => record,
+            None => match self.records.next() {
+                Some(record) => record?,
+                None => return Ok(None),
+            },
+        };
+
+        let Some(key) = key_of(self.path, &first).cloned() else {
+            return Ok(Some((None, vec![first])));
+        };
+
+        let mut group = vec![first];
+        for record in self.records.by_ref() {
+            let record = record?;
+            if key_of(self.path, &record)
+                .is_some_and(|k| compare_keys(Some(k), Some(&key)) == Ordering::Equal)
+            {
+                group.push(record);
+            } else {
+                self.pending = Some(record);
+                break;
+            }
+        }
+        Ok(Some((Some(key), group)))
+    }
+}
+
+impl JoinSummary {
+    pub fn show_report(&self, left_name: &str, right_name: &str) {
+        print!("{}", self.report(left_name, right_name));
+    }
+
+    /// The text of [`JoinSummary::show_report`], for printing somewhere other
+    /// than stdout when the joined records are written there.
+    pub fn report(&self, left_name: &str, right_name: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "===============================");
+        let _ = writeln!(
+            out,
+            "Joined {} with {}: {} rows written, {} matched pairs",
+            left_name,
+            right_name,
+            self.rows_written.to_formatted_string(&Locale::en),
+            self.matched_pairs.to_formatted_string(&Locale::en)
+        );
+
+        let mut show_keys = |side: &str, keys: &[Value], without_key: usize| {
+            let _ = writeln!(
+                out,
+                "Unmatched keys in {}: {} ({} records without a key)",
+                side,
+                keys.len().to_formatted_string(&Locale::en),
+                without_key.to_formatted_string(&Locale::en)
+            );
+            for key in keys {
+                let _ = writeln!(out, "\t{}", display_value(key));
+            }
+        };
+        show_keys(left_name, &self.unmatched_left_keys, self.left_without_key);
+        show_keys(
+            right_name,
+            &self.unmatched_right_keys,
+            self.right_without_key,
+        );
+        out
+    }
+}
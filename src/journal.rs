@@ -0,0 +1,131 @@
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+/// One recorded mutation of a record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub row: usize,
+    pub old: Value,
+    pub new: Value,
+    /// RFC 3339 UTC time at which the edit was made
+    pub timestamp: String,
+}
+
+impl JournalEntry {
+    pub fn new(row: usize, old: Value, new: Value) -> Self {
+        Self {
+            row,
+            old,
+            new,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        }
+    }
+}
+
+/// Ordered log of record edits with an undo/redo cursor.
+///
+/// Entries before the cursor are applied; entries after it have been undone
+/// and can be redone until a new edit is recorded.
+#[derive(Debug, Clone, Default)]
+pub struct EditJournal {
+    entries: Vec<JournalEntry>,
+    position: usize,
+}
+
+impl EditJournal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a new edit, discarding any undone entries.
+    pub fn record(&mut self, entry: JournalEntry) {
+        self.entries.truncate(self.position);
+        self.entries.push(entry);
+        self.position = self.entries.len();
+    }
+
+    /// Applied entries, oldest first
+    pub fn entries(&self) -> &[JournalEntry] {
+        &self.entries[..self.position]
+    }
+
+    pub fn len(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == 0
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position < self.entries.len()
+    }
+
+    /// Moves the cursor back one entry, returning the edit to revert.
+    pub fn step_back(&mut self) -> Option<&JournalEntry> {
+        if !self.can_undo() {
+            return None;
+        }
+        self.position -= 1;
+        self.entries.get(self.position)
+    }
+
+    /// Moves the cursor forward one entry, returning the edit to re-apply.
+    pub fn step_forward(&mut self) -> Option<&JournalEntry> {
+        if !self.can_redo() {
+            return None;
+        }
+        self.position += 1;
+        self.entries.get(self.position - 1)
+    }
+
+    /// Writes the applied entries as JSONL, one entry per line.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for entry in self.entries() {
+            serde_json::to_writer(&mut writer, entry)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Reads a journal previously written with [`EditJournal::write`].
+    pub fn read<B: BufRead>(reader: B) -> io::Result<Self> {
+        let mut journal = Self::new();
+        for line_result in reader.lines() {
+            let line = line_result?;
+            if !line.trim().is_empty() {
+                let entry: JournalEntry = serde_json::from_str(&line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                journal.entries.push(entry);
+            }
+        }
+        journal.position = journal.entries.len();
+        Ok(journal)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+/// Outcome of replaying a journal against a dataset
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplaySummary {
+    pub applied: usize,
+    /// Rows whose current value did not match the entry's `old` value
+    pub conflicts: Vec<usize>,
+}
@@ -1,13 +1,29 @@
+use crate::completeness::Completeness;
+use crate::duplicates::{DuplicateGroup, KeepPolicy, show_duplicate_groups};
+use crate::index::LineIndex;
+use crate::journal::{EditJournal, JournalEntry, ReplaySummary};
+use crate::parallel;
+use crate::path::KeyPath;
+use crate::profile::{StringProfile, merge_profiles, profile_records, show_string_profiles};
+use crate::progress::{ProgressCallback, ProgressTracker};
+use crate::query::Filter;
+use crate::sample::SampleInfo;
+use crate::transform::{Transform, TransformSummary};
+use memmap2::Mmap;
 use num_format::{Locale, ToFormattedString};
+use serde::Serialize;
+use serde::de::IgnoredAny;
 use serde_json::Value;
 use std::{
+    borrow::{Borrow, Cow},
     collections::{BTreeMap, HashMap, HashSet},
     fmt,
     fs::File,
-    io::{self, BufRead, BufReader},
-    path::PathBuf,
+    io::{self, BufRead, BufReader, Lines, Write},
+    path::{Path, PathBuf},
+    sync::OnceLock,
 };
-use tracing::{Level, error, info, span, warn};
+use tracing::{Level, error, span, warn};
 
 /// Custom error type for HTTP operations
 #[derive(Debug)]
@@ -67,6 +83,61 @@ pub trait JsonlReader {
     fn iter(&self) -> Box<dyn Iterator<Item = &Value> + '_>;
     fn source_name(&self) -> &str;
     fn push(&mut self, value: Value) -> Result<(), Self::Error>;
+
+    /// Sets the callback receiving the progress of `load`; readers that load
+    /// nothing worth reporting ignore it.
+    fn set_progress(&mut self, _callback: Option<ProgressCallback>) {}
+
+    /// Iterates over the records in order. Unlike [`JsonlReader::iter`], whose
+    /// references callers may keep, lazily parsing readers can hand out
+    /// temporaries, so a full pass never holds the whole input parsed; a
+    /// record that cannot be read is an error rather than a skipped row.
+    fn records(&self) -> Box<dyn Iterator<Item = Result<Cow<'_, Value>, Self::Error>> + '_> {
+        Box::new(self.iter().map(|record| Ok(Cow::Borrowed(record))))
+    }
+
+    /// Calls `f` with consecutive batches of [`JsonlReader::records`], in
+    /// order, and the row number of each batch's first record, stopping at
+    /// the first record that cannot be read.
+    fn for_each_batch(&self, f: &mut dyn FnMut(usize, &[&Value])) -> Result<(), Self::Error> {
+        let mut records = self.records();
+        let mut start = 0;
+        loop {
+            let batch = records
+                .by_ref()
+                .take(RECORD_BATCH_ROWS)
+                .collect::<Result<Vec<_>, _>>()?;
+            if batch.is_empty() {
+                return Ok(());
+            }
+            let rows: Vec<&Value> = batch.iter().map(|record| &**record).collect();
+            f(start, &rows);
+            start += batch.len();
+        }
+    }
+}
+
+/// Runs `f` over the records of `reader`, returning the error of the first
+/// record that cannot be read instead of its result.
+pub fn with_records<'a, R, T, F>(reader: &'a R, f: F) -> io::Result<T>
+where
+    R: JsonlReader + ?Sized,
+    F: FnOnce(&mut dyn Iterator<Item = Cow<'a, Value>>) -> T,
+{
+    let mut failed = None;
+    let mut records = reader.records().map_while(|record| match record {
+        Ok(record) => Some(record),
+        Err(e) => {
+            failed = Some(e);
+            None
+        }
+    });
+    let result = f(&mut records);
+    drop(records);
+    match failed {
+        Some(e) => Err(io::Error::other(e)),
+        None => Ok(result),
+    }
 }
 
 /// File-based JSONL reader (current implementation)
@@ -74,8 +145,17 @@ pub struct FileJsonlReader {
     path: PathBuf,
     filename: String,
     data: Vec<Value>,
+    threads: usize,
+    progress: Option<ProgressCallback>,
 }
 
+/// Lines per thread parsed in one batch by a multi-threaded [`FileJsonlReader`]
+const PARSE_BATCH_LINES: usize = 8192;
+/// Rows analyzed between two progress updates of a thread
+const PROGRESS_BATCH_ROWS: usize = 1024;
+/// Records held at once by [`JsonlReader::for_each_batch`]
+const RECORD_BATCH_ROWS: usize = 65_536;
+
 impl FileJsonlReader {
     pub fn new(path: PathBuf) -> Self {
         let filename = path
@@ -87,29 +167,64 @@ impl FileJsonlReader {
             path,
             filename,
             data: Vec::new(),
+            threads: 1,
+            progress: None,
         }
     }
+
+    /// Parses lines on `threads` threads; records keep their file order.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
 }
 
 impl JsonlReader for FileJsonlReader {
     type Error = io::Error;
 
     fn load(&mut self) -> Result<(), Self::Error> {
-        let file = File::open(&self.path)?;
-        let reader = BufReader::new(file);
-
         self.data.clear();
 
-        for line_result in reader.lines() {
-            let line = line_result?;
-            if !line.trim().is_empty() {
-                let json: Value = serde_json::from_str(&line)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                self.data.push(json);
+        if self.threads <= 1 {
+            for record in stream_jsonl_with_progress(&self.path, self.progress.clone())? {
+                self.data.push(record?);
             }
+            return Ok(());
         }
 
-        Ok(())
+        // Read a batch of lines, parse it split across the threads, append in order
+        let file = File::open(&self.path)?;
+        let progress = ProgressTracker::new("read", None, Some(file.metadata()?.len()))
+            .with_callback(self.progress.clone());
+        let mut lines = BufReader::new(file)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+            .map(|(i, line)| line.map(|line| (i + 1, line)));
+        loop {
+            let batch = lines
+                .by_ref()
+                .take(PARSE_BATCH_LINES * self.threads)
+                .collect::<io::Result<Vec<(usize, String)>>>()?;
+            if batch.is_empty() {
+                progress.finish();
+                return Ok(());
+            }
+            let bytes: usize = batch.iter().map(|(_, line)| line.len() + 1).sum();
+            let parsed = parallel::map_chunks(&batch, self.threads, |_, chunk| {
+                chunk
+                    .iter()
+                    .map(|(number, line)| {
+                        serde_json::from_str(line).map_err(|e| format!("line {}: {}", number, e))
+                    })
+                    .collect::<Result<Vec<Value>, _>>()
+            });
+            for records in parsed {
+                self.data
+                    .extend(records.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?);
+            }
+            progress.add(batch.len(), bytes as u64);
+        }
     }
 
     fn len(&self) -> usize {
@@ -148,6 +263,269 @@ impl JsonlReader for FileJsonlReader {
         self.data.push(value);
         Ok(())
     }
+
+    fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.progress = callback;
+    }
+}
+
+/// Memory-mapped JSONL reader for files too large to hold as parsed values.
+///
+/// `load` maps the file, indexes its records (reusing an up-to-date sidecar
+/// line index) and checks that every record is valid JSON without building
+/// values. Records handed out by `get` and `iter` are parsed on first access
+/// and cached until [`MmapJsonlReader::clear_cache`]; passes through `records`
+/// and `for_each_batch`, which the [`JsonlData`] analysis and the commands
+/// use, parse temporaries and cache nothing. Edited and pushed records are
+/// kept separately. The file must not be modified while it is mapped.
+pub struct MmapJsonlReader {
+    path: PathBuf,
+    filename: String,
+    mmap: Option<Mmap>,
+    index: Option<LineIndex>,
+    cache: Vec<OnceLock<Box<Value>>>,
+    edits: HashMap<usize, Value>,
+    pushed: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl MmapJsonlReader {
+    pub fn new(path: PathBuf) -> Self {
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            filename,
+            mmap: None,
+            index: None,
+            cache: Vec::new(),
+            edits: HashMap::new(),
+            pushed: 0,
+            progress: None,
+        }
+    }
+
+    /// Raw bytes of record `index` in the mapped file, without the line break
+    fn line(&self, index: usize) -> Option<&[u8]> {
+        let mmap = self.mmap.as_ref()?;
+        let start = self.index.as_ref()?.offset(index)? as usize;
+        let rest = &mmap[start..];
+        let end = rest.iter().position(|&b| b == b'\n').unwrap_or(rest.len());
+        Some(&rest[..end])
+    }
+
+    /// Parses record `index` from the file, bypassing the cache and any edits.
+    pub fn read_record(&self, index: usize) -> io::Result<Option<Value>> {
+        self.line(index)
+            .map(|line| {
+                serde_json::from_slice(line).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("record {}: {}", index, e),
+                    )
+                })
+            })
+            .transpose()
+    }
+
+    /// Record `index` as edited, cached or freshly parsed, without caching it
+    fn record(&self, index: usize) -> io::Result<Cow<'_, Value>> {
+        if let Some(value) = self.edits.get(&index) {
+            return Ok(Cow::Borrowed(value));
+        }
+        if let Some(value) = self.cache.get(index).and_then(OnceLock::get) {
+            return Ok(Cow::Borrowed(&**value));
+        }
+        match self.read_record(index)? {
+            Some(value) => Ok(Cow::Owned(value)),
+            None => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("record {} is missing from the mapped file", index),
+            )),
+        }
+    }
+
+    /// Drops the parsed records held in the cache; edits are kept.
+    pub fn clear_cache(&mut self) {
+        for cell in &mut self.cache {
+            cell.take();
+        }
+    }
+
+    /// Number of parsed records held in the cache
+    pub fn cached_records(&self) -> usize {
+        self.cache
+            .iter()
+            .filter(|cell| cell.get().is_some())
+            .count()
+    }
+}
+
+impl JsonlReader for MmapJsonlReader {
+    type Error = io::Error;
+
+    fn load(&mut self) -> Result<(), Self::Error> {
+        let file = File::open(&self.path)?;
+        // SAFETY: the map is read-only, and callers must not change the file while it is mapped
+        let mmap = unsafe { Mmap::map(&file)? };
+        let index = match LineIndex::load(&self.path) {
+            Ok(Some(index)) => index,
+            _ => LineIndex::build(&self.path)?,
+        };
+
+        self.cache = (0..index.len()).map(|_| OnceLock::new()).collect();
+        self.edits.clear();
+        self.pushed = 0;
+        self.mmap = Some(mmap);
+        self.index = Some(index);
+
+        let size = self.mmap.as_ref().map_or(0, |mmap| mmap.len() as u64);
+        let progress = ProgressTracker::new("read", Some(self.cache.len()), Some(size))
+            .with_callback(self.progress.clone());
+        for row in 0..self.cache.len() {
+            if let Some(line) = self.line(row) {
+                serde_json::from_slice::<IgnoredAny>(line).map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("record {}: {}", row, e))
+                })?;
+                progress.add(1, line.len() as u64 + 1);
+            }
+        }
+        progress.finish();
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.cache.len() + self.pushed
+    }
+
+    fn get(&self, index: usize) -> Option<&Value> {
+        if let Some(value) = self.edits.get(&index) {
+            return Some(value);
+        }
+        let cell = self.cache.get(index)?;
+        if let Some(value) = cell.get() {
+            return Some(value);
+        }
+        match self.record(index) {
+            Ok(value) => Some(cell.get_or_init(|| Box::new(value.into_owned()))),
+            Err(e) => {
+                error!("Failed to read {}: {}", self.filename, e);
+                None
+            }
+        }
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut Value> {
+        if !self.edits.contains_key(&index) {
+            let value = self.get(index)?.clone();
+            self.edits.insert(index, value);
+        }
+        // The edited copy now shadows the file, so the parsed one is not needed
+        if let Some(cell) = self.cache.get_mut(index) {
+            cell.take();
+        }
+        self.edits.get_mut(&index)
+    }
+
+    fn replace(&mut self, index: usize, value: Value) -> Result<(), Self::Error> {
+        if index < self.len() {
+            self.edits.insert(index, value);
+            if let Some(cell) = self.cache.get_mut(index) {
+                cell.take();
+            }
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Index {} out of bounds", index),
+            ))
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Value> + '_> {
+        Box::new((0..self.len()).filter_map(move |index| self.get(index)))
+    }
+
+    fn source_name(&self) -> &str {
+        &self.filename
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), Self::Error> {
+        self.edits.insert(self.len(), value);
+        self.pushed += 1;
+        Ok(())
+    }
+
+    fn set_progress(&mut self, callback: Option<ProgressCallback>) {
+        self.progress = callback;
+    }
+
+    fn records(&self) -> Box<dyn Iterator<Item = io::Result<Cow<'_, Value>>> + '_> {
+        Box::new((0..self.len()).map(move |index| self.record(index)))
+    }
+}
+
+/// Streams the records of a JSONL file one line at a time, skipping blank
+/// lines, without holding the file in memory.
+pub fn stream_jsonl(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Value>>> {
+    stream_jsonl_with_progress(path, None)
+}
+
+/// Like [`stream_jsonl`], reporting `read` progress to `callback`.
+pub fn stream_jsonl_with_progress(
+    path: &Path,
+    callback: Option<ProgressCallback>,
+) -> io::Result<impl Iterator<Item = io::Result<Value>>> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    Ok(JsonlStream {
+        lines: BufReader::new(file).lines(),
+        line: 0,
+        progress: ProgressTracker::new("read", None, Some(size)).with_callback(callback),
+    })
+}
+
+/// Iterator behind [`stream_jsonl`], reporting `read` progress as it goes
+struct JsonlStream {
+    lines: Lines<BufReader<File>>,
+    /// Number of the line last read, counting blank lines
+    line: usize,
+    progress: ProgressTracker,
+}
+
+impl Iterator for JsonlStream {
+    type Item = io::Result<Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            self.line += 1;
+            let bytes = line.len() as u64 + 1;
+            if line.trim().is_empty() {
+                self.progress.add(0, bytes);
+                continue;
+            }
+            self.progress.add(1, bytes);
+            return Some(serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", self.line, e),
+                )
+            }));
+        }
+    }
+}
+
+impl Drop for JsonlStream {
+    fn drop(&mut self) {
+        self.progress.finish();
+    }
 }
 
 /// In-memory JSONL reader
@@ -279,16 +657,51 @@ impl JsonlReader for HttpJsonlReader {
     }
 }
 
+/// Required key paths seen in the dataset but absent or null in one row;
+/// serialized as `{"row":3,"missing":["a","b.c"],"null":["d"]}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MissingKeys {
+    pub row: usize,
+    /// Sorted key paths absent from their (present) parent object
+    pub missing: Vec<String>,
+    /// Sorted key paths present with a `null` value
+    pub null: Vec<String>,
+}
+
 /// Main JsonlData structure, now generic over the reader backend
 pub struct JsonlData<R: JsonlReader> {
     pub reader: R,
     pub keys_seen: Option<HashSet<String>>,
     pub key_freqs: Option<Vec<(String, usize)>>,
     pub rows_with_missing_keys: Option<Vec<usize>>,
+    pub journal: EditJournal,
+    /// Set when the rows are a sample of a larger dataset
+    pub sample: Option<SampleInfo>,
+    /// Which keys rows need to count as complete in the missing-key analysis
+    pub completeness: Completeness,
+    /// Threads used for the key analysis; the results do not depend on it
+    pub threads: usize,
+    /// Receives the progress of loading and of the analysis passes
+    pub progress: Option<ProgressCallback>,
 }
 
 impl<R: JsonlReader> JsonlData<R> {
-    pub fn new(mut reader: R) -> Result<Self, R::Error> {
+    pub fn new(reader: R) -> Result<Self, R::Error> {
+        Self::with_threads(reader, 1)
+    }
+
+    /// Loads and analyzes the data, spreading the key analysis over `threads` threads.
+    pub fn with_threads(reader: R, threads: usize) -> Result<Self, R::Error> {
+        Self::with_progress(reader, threads, None)
+    }
+
+    /// Like [`JsonlData::with_threads`], reporting the progress of loading and
+    /// analyzing the data to `progress`.
+    pub fn with_progress(
+        mut reader: R,
+        threads: usize,
+        progress: Option<ProgressCallback>,
+    ) -> Result<Self, R::Error> {
         let span = span!(
             Level::INFO,
             "JsonlData::new",
@@ -296,6 +709,7 @@ impl<R: JsonlReader> JsonlData<R> {
         );
         let _enter = span.enter();
 
+        reader.set_progress(progress.clone());
         reader.load()?;
 
         let mut instance = Self {
@@ -303,16 +717,31 @@ impl<R: JsonlReader> JsonlData<R> {
             keys_seen: Some(HashSet::new()),
             key_freqs: Some(Vec::new()),
             rows_with_missing_keys: Some(Vec::new()),
+            journal: EditJournal::new(),
+            sample: None,
+            completeness: Completeness::default(),
+            threads: threads.max(1),
+            progress,
         };
 
         // Analyze the loaded data
-        instance.keys_seen = Some(instance.get_all_keys_seen_across_dataset());
-        instance.key_freqs = Some(instance.analyze_json_keys());
-        instance.rows_with_missing_keys = Some(instance.identify_rows_with_missing_keys());
+        instance.refresh_analysis();
 
         Ok(instance)
     }
 
+    /// Marks the data as a sample, so every report states that it is sample-based.
+    pub fn with_sample_info(mut self, info: SampleInfo) -> Self {
+        self.sample = Some(info);
+        self
+    }
+
+    fn show_sample_note(&self) {
+        if let Some(info) = &self.sample {
+            println!("NOTE: sample-based report ({})", info);
+        }
+    }
+
     pub fn filename(&self) -> &str {
         self.reader.source_name()
     }
@@ -329,16 +758,26 @@ impl<R: JsonlReader> JsonlData<R> {
         self.reader.get(index)
     }
 
+    /// Runs an analysis pass over the records; one that cannot be read ends
+    /// the pass with an error rather than shifting the rows after it.
+    fn for_each_batch(&self, f: &mut dyn FnMut(usize, &[&Value])) {
+        if let Err(e) = self.reader.for_each_batch(f) {
+            error!("Failed to read {}: {}", self.filename(), e);
+        }
+    }
+
     fn get_top_key_combinations(&self, n: usize) -> Vec<(Vec<String>, usize)> {
         let mut combination_freqs: HashMap<Vec<String>, usize> = HashMap::new();
 
-        for val in self.reader.iter() {
-            if let Value::Object(map) = val {
-                let mut keys: Vec<String> = map.keys().cloned().collect();
-                keys.sort();
-                *combination_freqs.entry(keys).or_insert(0) += 1;
+        self.for_each_batch(&mut |_, rows| {
+            for val in rows {
+                if let Value::Object(map) = val {
+                    let mut keys: Vec<String> = map.keys().cloned().collect();
+                    keys.sort();
+                    *combination_freqs.entry(keys).or_insert(0) += 1;
+                }
             }
-        }
+        });
 
         let mut sorted_combinations: Vec<(Vec<String>, usize)> =
             combination_freqs.into_iter().collect();
@@ -348,96 +787,147 @@ impl<R: JsonlReader> JsonlData<R> {
     }
 
     fn analyze_json_keys(&self) -> Vec<(String, usize)> {
+        let progress = ProgressTracker::new("keys", Some(self.reader.len()), None)
+            .with_callback(self.progress.clone());
+        let threads = self.threads;
         let mut key_counts: BTreeMap<String, usize> = BTreeMap::new();
-
-        for value in self.reader.iter() {
-            self.collect_row_keys(value, &mut key_counts, String::new());
-        }
-
-        let mut sorted_keys: Vec<(String, usize)> = key_counts.into_iter().collect();
-        sorted_keys.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
-
-        sorted_keys
-    }
-
-    fn collect_row_keys(
-        &self,
-        value: &Value,
-        key_counts: &mut BTreeMap<String, usize>,
-        prefix: String,
-    ) {
-        match value {
-            Value::Object(map) => {
-                for (key, val) in map {
-                    let full_key = if prefix.is_empty() {
-                        key.clone()
-                    } else {
-                        format!("{}.{}", prefix, key)
-                    };
-
-                    *key_counts.entry(full_key.clone()).or_insert(0) += 1;
-                    self.collect_row_keys(val, key_counts, full_key);
+        self.for_each_batch(&mut |_, rows| {
+            let partial_counts = parallel::map_chunks(rows, threads, |_, chunk| {
+                let mut key_counts: BTreeMap<String, usize> = BTreeMap::new();
+                for batch in chunk.chunks(PROGRESS_BATCH_ROWS) {
+                    for value in batch {
+                        count_key_paths(value, &mut key_counts);
+                    }
+                    progress.add(batch.len(), 0);
                 }
-            }
-            Value::Array(arr) => {
-                for (index, val) in arr.iter().enumerate() {
-                    let array_key = if prefix.is_empty() {
-                        format!("[{}]", index)
-                    } else {
-                        format!("{}[{}]", prefix, index)
-                    };
-                    self.collect_row_keys(val, key_counts, array_key);
+                key_counts
+            });
+            for partial in partial_counts {
+                for (key, count) in partial {
+                    *key_counts.entry(key).or_insert(0) += count;
                 }
             }
-            _ => {}
-        }
+        });
+        progress.finish();
+
+        rank_key_counts(key_counts)
     }
 
     fn get_keys_in_row(&self, value: &Value) -> HashSet<String> {
         let mut keys = HashSet::new();
-        self.collect_keys_from_value(value, &mut keys, String::new());
+        collect_key_paths(value, &mut keys, String::new());
         keys
     }
 
-    fn collect_keys_from_value(&self, value: &Value, keys: &mut HashSet<String>, prefix: String) {
-        match value {
-            Value::Object(map) => {
-                for (k, v) in map {
-                    let full_key = if prefix.is_empty() {
-                        k.clone()
-                    } else {
-                        format!("{}.{}", prefix, k)
-                    };
-                    keys.insert(full_key.clone());
-                    self.collect_keys_from_value(v, keys, full_key);
+    /// Runs `check` on every row on the configured threads, keeping the rows
+    /// it returns something for, in row order.
+    fn scan_rows<T, F>(&self, stage: &'static str, check: F) -> Vec<(usize, T)>
+    where
+        T: Send,
+        F: Fn(&Value) -> Option<T> + Sync,
+    {
+        let progress = ProgressTracker::new(stage, Some(self.reader.len()), None)
+            .with_callback(self.progress.clone());
+        let threads = self.threads;
+
+        let mut found = Vec::new();
+        self.for_each_batch(&mut |start, rows| {
+            let partial = parallel::map_chunks(rows, threads, |offset, chunk| {
+                let mut found = Vec::new();
+                for (batch_index, batch) in chunk.chunks(PROGRESS_BATCH_ROWS).enumerate() {
+                    let batch_offset = start + offset + batch_index * PROGRESS_BATCH_ROWS;
+                    for (i, v) in batch.iter().enumerate() {
+                        if let Some(result) = check(v) {
+                            found.push((batch_offset + i, result));
+                        }
+                    }
+                    progress.add(batch.len(), 0);
                 }
+                found
+            });
+            found.extend(partial.into_iter().flatten());
+        });
+        progress.finish();
+        found
+    }
+
+    fn identify_rows_with_missing_keys(&self, all_keys: &HashSet<String>) -> Vec<usize> {
+        let completeness = &self.completeness;
+        self.scan_rows("missing keys", |v| {
+            completeness.check(v, all_keys).map(|_| ())
+        })
+        .into_iter()
+        .map(|(row, _)| row)
+        .collect()
+    }
+
+    /// For every incomplete row (see [`Completeness`]), the required key
+    /// paths it lacks or has as null, in row order.
+    pub fn missing_keys_by_row(&self) -> Vec<MissingKeys> {
+        let all_keys = self.keys_seen.clone().unwrap_or_default();
+        let completeness = &self.completeness;
+        self.scan_rows("missing keys", |v| completeness.check(v, &all_keys))
+            .into_iter()
+            .map(|(row, (missing, null))| MissingKeys { row, missing, null })
+            .collect()
+    }
+
+    /// Prints how many rows are incomplete and, per key path, how many lack it
+    /// or have it as null.
+    pub fn show_missing_keys_report(&self, missing_keys: &[MissingKeys]) {
+        println!("===============================");
+        self.show_sample_note();
+        println!(
+            "{} of {} rows in {} are missing required keys",
+            missing_keys.len().to_formatted_string(&Locale::en),
+            self.len().to_formatted_string(&Locale::en),
+            self.filename()
+        );
+        let mut counts: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+        for entry in missing_keys {
+            for key in &entry.missing {
+                counts.entry(key).or_default().0 += 1;
             }
-            Value::Array(arr) => {
-                for (i, v) in arr.iter().enumerate() {
-                    let array_key = if prefix.is_empty() {
-                        format!("[{}]", i)
-                    } else {
-                        format!("{}[{}]", prefix, i)
-                    };
-                    self.collect_keys_from_value(v, keys, array_key);
-                }
+            for key in &entry.null {
+                counts.entry(key).or_default().1 += 1;
             }
-            _ => {}
+        }
+        let width = counts.keys().map(|k| k.len()).max().unwrap_or(20).max(20);
+        println!(
+            "{:<width$} {:>12} {:>12}",
+            "Key",
+            "Absent",
+            "Null",
+            width = width
+        );
+        println!("{}", "-".repeat(width + 26));
+        for (key, (absent, null)) in counts {
+            println!(
+                "\t{:<width$} {:>12} {:>12}",
+                key,
+                absent.to_formatted_string(&Locale::en),
+                null.to_formatted_string(&Locale::en),
+                width = width
+            );
         }
     }
 
-    fn identify_rows_with_missing_keys(&self) -> Vec<usize> {
-        let mut rows_with_missing_keys = Vec::new();
-        let all_keys = self.get_all_keys_seen_across_dataset();
+    /// Changes which keys are required and recomputes `rows_with_missing_keys`.
+    pub fn set_completeness(&mut self, completeness: Completeness) {
+        self.completeness = completeness;
+        let all_keys = self.keys_seen.clone().unwrap_or_default();
+        self.rows_with_missing_keys = Some(self.identify_rows_with_missing_keys(&all_keys));
+    }
 
-        for (i, v) in self.reader.iter().enumerate() {
-            let row_keys = self.get_keys_in_row(v);
-            let missing_keys: HashSet<_> = all_keys.difference(&row_keys).collect();
-            if !missing_keys.is_empty() {
-                rows_with_missing_keys.push(i);
-            }
+    /// Writes [`Self::missing_keys_by_row`] as JSONL, returning the number of rows written.
+    pub fn write_missing_keys<W: Write>(&self, mut writer: W) -> io::Result<usize> {
+        let missing_keys = self.missing_keys_by_row();
+        for entry in &missing_keys {
+            serde_json::to_writer(&mut writer, entry)?;
+            writer.write_all(b"\n")?;
         }
-        rows_with_missing_keys
+        writer.flush()?;
+        Ok(missing_keys.len())
     }
 
     fn get_all_keys_seen_across_dataset(&self) -> HashSet<String> {
@@ -453,6 +943,7 @@ impl<R: JsonlReader> JsonlData<R> {
         );
         let _ = span.enter();
         println!("===============================");
+        self.show_sample_note();
         println!(
             "Found {} unique JSON keys in file {}",
             self.keys_seen.as_ref().map_or(0, |k| k.len()),
@@ -469,6 +960,7 @@ impl<R: JsonlReader> JsonlData<R> {
         let _ = span.enter();
 
         println!("===============================");
+        self.show_sample_note();
 
         if let Some(ref key_freqs) = self.key_freqs {
             let max_key_len = key_freqs
@@ -489,12 +981,41 @@ impl<R: JsonlReader> JsonlData<R> {
         println!("Rows with missing keys: {:?}", self.rows_with_missing_keys);
     }
 
+    /// Length, padding and pattern statistics of the string values of every
+    /// scalar key path, computed on the configured threads.
+    pub fn string_profiles(&self) -> BTreeMap<String, StringProfile> {
+        let threads = self.threads;
+        let mut parts = Vec::new();
+        self.for_each_batch(&mut |_, rows| {
+            parts.extend(parallel::map_chunks(rows, threads, |_, chunk| {
+                profile_records(chunk.iter().copied())
+            }));
+        });
+        merge_profiles(parts)
+    }
+
+    pub fn show_string_profile_report(&self) {
+        let span = span!(
+            Level::INFO,
+            "show_string_profile_report",
+            filename = self.filename()
+        );
+        let _ = span.enter();
+
+        println!("===============================");
+        self.show_sample_note();
+        println!("String values per key in {}", self.filename());
+        show_string_profiles(&self.string_profiles());
+    }
+
     pub fn show_top_key_combinations_report(&self, n: usize) {
         let span = span!(
             Level::INFO,
             "show_top_key_combinations_report",
             filename = self.filename()
         );
+        let _ = span.enter();
+        self.show_sample_note();
         println!(
             "Top {} Most Frequent JSON Key combinations in {}",
             n,
@@ -533,13 +1054,16 @@ impl<R: JsonlReader> JsonlData<R> {
             );
 
             let all_keys = self.get_all_keys_seen_across_dataset();
-            let row_keys = self.get_keys_in_row(row);
-            let missing_keys: Vec<_> = all_keys.difference(&row_keys).collect();
-
-            if !missing_keys.is_empty() {
-                warn!("Missing keys in this record: {:?}", missing_keys);
-            } else {
-                println!("This record contains all keys found in the dataset.");
+            match self.completeness.check(row, &all_keys) {
+                Some((missing, null)) => {
+                    if !missing.is_empty() {
+                        warn!("Missing keys in this record: {:?}", missing);
+                    }
+                    if !null.is_empty() {
+                        warn!("Null keys in this record: {:?}", null);
+                    }
+                }
+                None => println!("This record contains all required keys found in the dataset."),
             }
         } else {
             error!("Record {} not found", record_id);
@@ -547,15 +1071,326 @@ impl<R: JsonlReader> JsonlData<R> {
     }
 
     pub fn replace_record(&mut self, record_id: usize, new_json: Value) -> Result<(), R::Error> {
-        self.reader.replace(record_id, new_json)?;
+        self.set_record(record_id, new_json)?;
 
         // Recompute analysis after replacement
-        self.keys_seen = Some(self.get_all_keys_seen_across_dataset());
-        self.key_freqs = Some(self.analyze_json_keys());
-        self.rows_with_missing_keys = Some(self.identify_rows_with_missing_keys());
+        self.refresh_analysis();
+
+        Ok(())
+    }
+
+    /// Applies bulk key transformations to every record. With `dry_run` the
+    /// records are left untouched and only the per-rule row counts are reported.
+    pub fn apply_transform(
+        &mut self,
+        transform: &Transform,
+        dry_run: bool,
+    ) -> Result<TransformSummary, R::Error> {
+        let span = span!(Level::INFO, "apply_transform", filename = self.filename());
+        let _ = span.enter();
+
+        let mut summary = TransformSummary::new(transform, dry_run);
+
+        // Only changed records are kept until the pass is over
+        let mut changed = Vec::new();
+        for (index, record) in self.reader.records().enumerate() {
+            let mut updated = record?.into_owned();
+            let outcomes = transform.apply(&mut updated);
+            summary.record_row(&outcomes);
+
+            if !dry_run && outcomes.iter().any(|o| o.changed > 0) {
+                changed.push((index, updated));
+            }
+        }
+        for (index, updated) in changed {
+            self.set_record(index, updated)?;
+        }
+
+        if !dry_run {
+            self.refresh_analysis();
+        }
+
+        Ok(summary)
+    }
+
+    /// Reverts the most recent journaled edit, returning the affected row.
+    pub fn undo(&mut self) -> Result<Option<usize>, R::Error> {
+        let Some(entry) = self.journal.step_back().cloned() else {
+            return Ok(None);
+        };
+        if let Err(e) = self.reader.replace(entry.row, entry.old) {
+            self.journal.step_forward();
+            return Err(e);
+        }
+        self.refresh_analysis();
+        Ok(Some(entry.row))
+    }
+
+    /// Re-applies the most recently undone edit, returning the affected row.
+    pub fn redo(&mut self) -> Result<Option<usize>, R::Error> {
+        let Some(entry) = self.journal.step_forward().cloned() else {
+            return Ok(None);
+        };
+        if let Err(e) = self.reader.replace(entry.row, entry.new) {
+            self.journal.step_back();
+            return Err(e);
+        }
+        self.refresh_analysis();
+        Ok(Some(entry.row))
+    }
+
+    /// Applies the edits of a saved journal to this copy of the data. Entries
+    /// whose `old` value does not match the current row are skipped as
+    /// conflicts unless `force` is set. Replayed edits are journaled too.
+    ///
+    /// The replay is all or nothing: if an edit cannot be applied (a row out
+    /// of range), the edits applied before it are rolled back and neither the
+    /// data nor the journal changes.
+    pub fn replay_journal(
+        &mut self,
+        journal: &EditJournal,
+        force: bool,
+    ) -> Result<ReplaySummary, R::Error> {
+        let span = span!(Level::INFO, "replay_journal", filename = self.filename());
+        let _ = span.enter();
+
+        let mut summary = ReplaySummary::default();
+        let mut applied: Vec<JournalEntry> = Vec::new();
+
+        for entry in journal.entries() {
+            let current = self.reader.get(entry.row).cloned();
+            if current.as_ref() != Some(&entry.old) && !force {
+                warn!("Journal conflict at row {}: record has changed", entry.row);
+                summary.conflicts.push(entry.row);
+                continue;
+            }
+            if let Err(e) = self.reader.replace(entry.row, entry.new.clone()) {
+                for undone in applied.into_iter().rev() {
+                    // These rows were just replaced, so restoring them cannot fail
+                    let _ = self.reader.replace(undone.row, undone.old);
+                }
+                return Err(e);
+            }
+            let old = current.unwrap_or(Value::Null);
+            applied.push(JournalEntry::new(entry.row, old, entry.new.clone()));
+            summary.applied += 1;
+        }
+
+        for entry in applied {
+            self.journal.record(entry);
+        }
+        self.refresh_analysis();
+        Ok(summary)
+    }
 
+    /// Groups row numbers by a per-row identity, in order of first appearance.
+    /// Rows for which `identity` returns `None` are left out.
+    fn group_rows_by<F>(&self, identity: F) -> Vec<DuplicateGroup>
+    where
+        F: Fn(usize, &Value) -> Option<String>,
+    {
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        let mut groups: Vec<DuplicateGroup> = Vec::new();
+
+        self.for_each_batch(&mut |start, rows| {
+            for (i, v) in rows.iter().enumerate() {
+                let i = start + i;
+                let Some(key) = identity(i, v) else {
+                    continue;
+                };
+                match positions.get(&key) {
+                    Some(&pos) => groups[pos].rows.push(i),
+                    None => {
+                        positions.insert(key.clone(), groups.len());
+                        groups.push(DuplicateGroup { key, rows: vec![i] });
+                    }
+                }
+            }
+        });
+        groups
+    }
+
+    /// Groups of rows that are identical once key order is canonicalized.
+    pub fn find_duplicate_rows(&self) -> Vec<DuplicateGroup> {
+        // serde_json maps are key-sorted, so serialization is already canonical
+        self.group_rows_by(|_, v| Some(v.to_string()))
+            .into_iter()
+            .filter(|g| g.rows.len() > 1)
+            .collect()
+    }
+
+    /// Groups of rows sharing a value for the primary key `key`. Rows
+    /// without the key are never considered duplicates.
+    pub fn find_duplicate_keys(&self, key: &KeyPath) -> Vec<DuplicateGroup> {
+        self.group_rows_by(|_, v| key.get(v).map(|k| k.to_string()))
+            .into_iter()
+            .filter(|g| g.rows.len() > 1)
+            .collect()
+    }
+
+    /// Row numbers that survive deduplication, in file order. Duplicates are
+    /// exact rows, or rows sharing `key` when given.
+    pub fn dedup_rows(&self, key: Option<&KeyPath>, keep: KeepPolicy) -> Vec<usize> {
+        let groups = match key {
+            Some(key) => self.group_rows_by(|i, v| {
+                // Rows without the key are unique: give each its own group,
+                // under an identity no JSON value serializes to
+                Some(
+                    key.get(v)
+                        .map_or_else(|| format!("\0{}", i), |k| k.to_string()),
+                )
+            }),
+            None => self.group_rows_by(|_, v| Some(v.to_string())),
+        };
+
+        let mut kept: Vec<usize> = groups
+            .iter()
+            .filter_map(|group| match keep {
+                KeepPolicy::First => group.rows.first().copied(),
+                KeepPolicy::Last => group.rows.last().copied(),
+                KeepPolicy::MostKeys => group.rows.iter().copied().max_by(|&a, &b| {
+                    let count = |row| {
+                        self.reader
+                            .get(row)
+                            .map_or(0, |v| self.get_keys_in_row(v).len())
+                    };
+                    // Prefer the earlier row on ties
+                    count(a).cmp(&count(b)).then(b.cmp(&a))
+                }),
+            })
+            .collect();
+        kept.sort_unstable();
+        kept
+    }
+
+    pub fn show_duplicates_report(&self, key: Option<&KeyPath>) {
+        let span = span!(
+            Level::INFO,
+            "show_duplicates_report",
+            filename = self.filename()
+        );
+        let _ = span.enter();
+
+        self.show_sample_note();
+        show_duplicate_groups(
+            &format!("Exact duplicate rows in {}", self.filename()),
+            &self.find_duplicate_rows(),
+        );
+        if let Some(key) = key {
+            show_duplicate_groups(
+                &format!("Rows sharing a value of {} in {}", key, self.filename()),
+                &self.find_duplicate_keys(key),
+            );
+        }
+    }
+
+    /// Copies the records matching `filter` into a new in-memory dataset, so
+    /// the key reports can be run on just that subset.
+    pub fn filtered(&self, filter: &Filter) -> io::Result<JsonlData<MemoryJsonlReader>> {
+        let name = format!("{} where {}", self.filename(), filter);
+        let records = with_records(&self.reader, |records| {
+            records
+                .filter(|record| filter.matches(record))
+                .map(Cow::into_owned)
+                .collect()
+        })?;
+        JsonlData::new(MemoryJsonlReader::new(name, records))
+    }
+
+    /// Writes every record as one compact JSON document per line.
+    pub fn write_jsonl<W: Write>(&self, writer: W) -> io::Result<usize> {
+        with_records(&self.reader, |records| write_jsonl(writer, records))?
+    }
+
+    /// Replaces a record and records the edit in the journal.
+    fn set_record(&mut self, index: usize, value: Value) -> Result<(), R::Error> {
+        let old = self.reader.get(index).cloned().unwrap_or(Value::Null);
+        self.reader.replace(index, value.clone())?;
+        self.journal.record(JournalEntry::new(index, old, value));
         Ok(())
     }
+
+    fn refresh_analysis(&mut self) {
+        let key_freqs = self.analyze_json_keys();
+        let keys_seen: HashSet<String> = key_freqs.iter().map(|(key, _)| key.clone()).collect();
+        self.rows_with_missing_keys = Some(self.identify_rows_with_missing_keys(&keys_seen));
+        self.keys_seen = Some(keys_seen);
+        self.key_freqs = Some(key_freqs);
+    }
+}
+
+/// Adds the key paths of a record (`a.b`, `list[0].c`) below `prefix` to `keys`.
+pub(crate) fn collect_key_paths(value: &Value, keys: &mut HashSet<String>, prefix: String) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                let full_key = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                keys.insert(full_key.clone());
+                collect_key_paths(v, keys, full_key);
+            }
+        }
+        Value::Array(arr) => {
+            for (i, v) in arr.iter().enumerate() {
+                let array_key = if prefix.is_empty() {
+                    format!("[{}]", i)
+                } else {
+                    format!("{}[{}]", prefix, i)
+                };
+                collect_key_paths(v, keys, array_key);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Counts `value` once for each key path it contains.
+fn count_key_paths(value: &Value, counts: &mut BTreeMap<String, usize>) {
+    let mut keys = HashSet::new();
+    collect_key_paths(value, &mut keys, String::new());
+    for key in keys {
+        *counts.entry(key).or_insert(0) += 1;
+    }
+}
+
+/// Most frequent first, ties alphabetical
+fn rank_key_counts(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut sorted_keys: Vec<(String, usize)> = counts.into_iter().collect();
+    sorted_keys.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    sorted_keys
+}
+
+/// Rows containing each key path (`items[0].id`), ranked as in the key report
+pub fn key_frequencies<I>(records: I) -> Vec<(String, usize)>
+where
+    I: IntoIterator,
+    I::Item: Borrow<Value>,
+{
+    let mut counts = BTreeMap::new();
+    for record in records {
+        count_key_paths(record.borrow(), &mut counts);
+    }
+    rank_key_counts(counts)
+}
+
+/// Writes records as JSONL, returning the number of lines written.
+pub fn write_jsonl<W, I>(mut writer: W, records: I) -> io::Result<usize>
+where
+    W: Write,
+    I: IntoIterator,
+    I::Item: Borrow<Value>,
+{
+    let mut count = 0;
+    for record in records {
+        serde_json::to_writer(&mut writer, record.borrow())?;
+        writer.write_all(b"\n")?;
+        count += 1;
+    }
+    writer.flush()?;
+    Ok(count)
 }
 
 // Type aliases for convenience
@@ -1,13 +1,73 @@
+pub mod aggregate;
+pub mod canonical;
+pub mod completeness;
+pub mod convert;
+pub mod documents;
+pub mod duplicates;
+pub mod external;
+pub mod follow;
+pub mod import;
+pub mod index;
+pub mod join;
+pub mod journal;
 pub mod jsonl;
+pub mod parallel;
+pub mod parquet_export;
+pub mod path;
+pub mod profile;
+pub mod progress;
+pub mod project;
+pub mod query;
+pub mod rules;
+pub mod sample;
+pub mod sort;
+pub mod split;
+pub mod timestamps;
+pub mod transform;
+pub mod value;
 
-use jsonl::{FileJsonlReader, HttpJsonlReader, JsonlData, MemoryJsonlReader};
+use aggregate::GroupBy;
+use canonical::{Canonicalizer, KeyOrder};
+use completeness::Completeness;
+use convert::{ConvertTo, TableExport, TableFormat};
+use documents::JsonDocumentReader;
+use duplicates::KeepPolicy;
+use follow::{FollowEvent, Follower};
+use import::{CsvJsonlReader, CsvOptions};
+use index::{LineIndex, RowSelection};
+use join::{ConflictPolicy, Join, JoinKind, JoinStrategy};
+use journal::EditJournal;
+use jsonl::{
+    FileJsonlReader, HttpJsonlReader, JsonlData, JsonlReader, MemoryJsonlReader, MmapJsonlReader,
+};
+use parquet_export::ParquetExport;
+use path::KeyPath;
+use project::Projection;
+use query::Filter;
+use rules::RuleSet;
+use sample::{SampleSize, Sampler};
+use sort::SortSpec;
+use split::{SplitMode, Splitter};
+use timestamps::{Bucket, Timeline};
+use transform::Transform;
 
-use std::{env, path::PathBuf};
-use tracing::{Level, error, info, span};
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    env,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::{Level, error, info, span, warn};
 use tracing_subscriber::{self, fmt::format::FmtSpan};
 
 fn print_welcome() {
-    println!(
+    // stderr, so that stdout can carry JSONL output
+    eprintln!(
         "Welcome to {} (Version {})!",
         env!("CARGO_PKG_NAME"),
         env!("CARGO_PKG_VERSION")
@@ -16,51 +76,953 @@ fn print_welcome() {
 
 /// Initializes tracing subscriber for logging.
 pub fn init_tracing() {
-    tracing_subscriber::fmt()
+    let initialized = tracing_subscriber::fmt()
         .with_max_level(Level::WARN)
         .with_span_events(FmtSpan::ENTER | FmtSpan::EXIT)
-        .init(); // No logging will print prior to this line!
-    print_welcome();
+        .with_writer(io::stderr)
+        .try_init(); // No logging will print prior to this line!
+    if initialized.is_ok() {
+        print_welcome();
+    }
+}
+
+/// Opens the output destination of a command: the given file, or stdout.
+fn open_output(output: Option<&str>) -> io::Result<Box<dyn Write>> {
+    match output {
+        Some(path) => Ok(Box::new(BufWriter::new(File::create(path)?))),
+        None => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+    }
+}
+
+fn show_reports<R: JsonlReader>(data: &JsonlData<R>) {
+    data.show_keys_found_report();
+    data.show_keys_frequencies_report();
+    data.show_top_key_combinations_report(5);
+    // Example of showing a specific record (optional)
+    // let record_id = 10;
+    // data.show_record(record_id);
+}
+
+/// Runs a command against loaded JSONL data, whatever its backend. The status
+/// is a failure when `validate` finds violations or cannot check the rules.
+pub fn run_command<R: JsonlReader>(data: JsonlData<R>, command: &Command) -> ExitCode {
+    if let Command::Validate { rules } = command {
+        return run_validate(&data, rules);
+    }
+    run_data_command(data, command);
+    ExitCode::SUCCESS
+}
+
+/// Runs the commands that do not decide the exit status.
+fn run_data_command<R: JsonlReader>(mut data: JsonlData<R>, command: &Command) {
+    match command {
+        Command::Report => show_reports(&data),
+        Command::Follow { .. } => error!("follow needs a growing file: use --filename=<path>"),
+        Command::Fmt {
+            order,
+            ascii,
+            check,
+            in_place,
+            output,
+        } => {
+            if *check || *in_place {
+                error!("fmt --check and --in-place need a JSONL file: use --filename=<path>");
+                return;
+            }
+            let mut canonicalizer = match jsonl::with_records(&data.reader, |records| {
+                Canonicalizer::new(order, records)
+            }) {
+                Ok(canonicalizer) => canonicalizer,
+                Err(e) => {
+                    error!("Failed to read records: {}", e);
+                    return;
+                }
+            };
+            canonicalizer.ascii = *ascii;
+            let written = open_output(output.as_deref()).and_then(|mut writer| {
+                jsonl::with_records(&data.reader, |records| {
+                    for record in records {
+                        writeln!(writer, "{}", canonicalizer.format(&record))?;
+                    }
+                    writer.flush()
+                })?
+            });
+            if let Err(e) = written {
+                error!("Failed to write records: {}", e);
+            }
+        }
+        Command::Transform {
+            rules,
+            dry_run,
+            output,
+            journal,
+        } => {
+            let transform = match Transform::parse(rules) {
+                Ok(transform) => transform,
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+            match data.apply_transform(&transform, *dry_run) {
+                Ok(summary) => {
+                    // Keep the report off stdout when the records are written there
+                    if *dry_run || output.is_some() {
+                        summary.show_report(data.filename());
+                    } else {
+                        eprint!("{}", summary.report(data.filename()));
+                    }
+                    if !*dry_run {
+                        let written = open_output(output.as_deref())
+                            .and_then(|writer| data.write_jsonl(writer));
+                        if let Err(e) = written {
+                            error!("Failed to write transformed records: {}", e);
+                        }
+                        save_journal(&data, journal.as_deref());
+                    }
+                }
+                Err(e) => error!("Failed to transform records: {}", e),
+            }
+        }
+        Command::Replay {
+            journal,
+            force,
+            output,
+        } => {
+            let edits = match EditJournal::load(journal) {
+                Ok(edits) => edits,
+                Err(e) => {
+                    error!("Failed to read journal {}: {}", journal, e);
+                    return;
+                }
+            };
+            match data.replay_journal(&edits, *force) {
+                Ok(summary) => {
+                    eprintln!(
+                        "Replayed {} of {} journal entries ({} conflicts: {:?})",
+                        summary.applied,
+                        edits.len(),
+                        summary.conflicts.len(),
+                        summary.conflicts
+                    );
+                    let written =
+                        open_output(output.as_deref()).and_then(|writer| data.write_jsonl(writer));
+                    if let Err(e) = written {
+                        error!("Failed to write replayed records: {}", e);
+                    }
+                }
+                Err(e) => error!("Failed to replay journal: {}", e),
+            }
+        }
+        Command::Profile => data.show_string_profile_report(),
+        Command::Timestamps { key, gap, bucket } => {
+            let keys = if let Some(key) = key {
+                let path = match KeyPath::parse(key) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        error!("{}", e);
+                        return;
+                    }
+                };
+                // A named key is taken as long as most of its values are timestamps
+                let format = jsonl::with_records(&data.reader, |records| {
+                    timestamps::majority_format(records, &path)
+                });
+                match format {
+                    Ok(Some(format)) => vec![(key.clone(), format)],
+                    Ok(None) => {
+                        error!("{} does not hold timestamps", key);
+                        return;
+                    }
+                    Err(e) => {
+                        error!("Failed to read records: {}", e);
+                        return;
+                    }
+                }
+            } else {
+                match jsonl::with_records(&data.reader, |records| {
+                    timestamps::detect_timestamp_keys(records)
+                }) {
+                    Ok(keys) if keys.is_empty() => {
+                        warn!("No keys holding timestamps found in {}", data.filename());
+                        keys
+                    }
+                    Ok(keys) => keys,
+                    Err(e) => {
+                        error!("Failed to read records: {}", e);
+                        return;
+                    }
+                }
+            };
+            for (path, format) in keys {
+                let path = match KeyPath::parse(&path) {
+                    Ok(path) => path,
+                    Err(e) => {
+                        error!("{}", e);
+                        continue;
+                    }
+                };
+                match jsonl::with_records(&data.reader, |records| {
+                    Timeline::analyze(records, &path, format, *gap, *bucket)
+                }) {
+                    Ok(timeline) => timeline.show_report(),
+                    Err(e) => error!("Failed to read records: {}", e),
+                }
+            }
+        }
+        Command::Validate { .. } => unreachable!("validate is run by run_command"),
+        Command::Duplicates { key } => {
+            match key.as_deref().map(KeyPath::parse_single).transpose() {
+                Ok(key) => data.show_duplicates_report(key.as_ref()),
+                Err(e) => error!("{}", e),
+            }
+        }
+        Command::MissingKeys {
+            completeness,
+            report,
+            output,
+        } => {
+            data.set_completeness(completeness.clone());
+            if *report {
+                data.show_missing_keys_report(&data.missing_keys_by_row());
+                return;
+            }
+            match open_output(output.as_deref()).and_then(|writer| data.write_missing_keys(writer))
+            {
+                Ok(rows) => eprintln!("{} of {} rows are missing keys", rows, data.len()),
+                Err(e) => error!("Failed to write missing keys: {}", e),
+            }
+        }
+        Command::Dedup { key, keep, output } => {
+            let key = match key.as_deref().map(KeyPath::parse_single).transpose() {
+                Ok(key) => key,
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+            let kept = data.dedup_rows(key.as_ref(), *keep);
+            let written = open_output(output.as_deref()).and_then(|writer| {
+                jsonl::write_jsonl(writer, kept.iter().filter_map(|&row| data.get(row)))
+            });
+            match written {
+                Ok(count) => eprintln!("Kept {} of {} rows", count, data.len()),
+                Err(e) => error!("Failed to write deduplicated records: {}", e),
+            }
+        }
+        Command::Filter { expression, mode } => run_filter(&data, expression, mode),
+        Command::Convert {
+            to,
+            columns,
+            output,
+        } => {
+            let written = match to {
+                ConvertTo::Jsonl => {
+                    open_output(output.as_deref()).and_then(|writer| data.write_jsonl(writer))
+                }
+                ConvertTo::Table(format) => {
+                    let export = match columns {
+                        Some(columns) => TableExport {
+                            format: *format,
+                            columns: columns.clone(),
+                        },
+                        None => match jsonl::with_records(&data.reader, |records| {
+                            TableExport::discover(*format, records)
+                        }) {
+                            Ok(export) => export,
+                            Err(e) => {
+                                error!("Failed to read records: {}", e);
+                                return;
+                            }
+                        },
+                    };
+                    open_output(output.as_deref()).and_then(|writer| {
+                        jsonl::with_records(&data.reader, |records| export.write(records, writer))?
+                    })
+                }
+                ConvertTo::Parquet => {
+                    // parse_command makes --output mandatory for Parquet
+                    let destination = output.as_deref().unwrap_or_default();
+                    let export = match jsonl::with_records(&data.reader, |records| {
+                        ParquetExport::infer(records)
+                    }) {
+                        Ok(export) => export,
+                        Err(e) => {
+                            error!("Failed to read records: {}", e);
+                            return;
+                        }
+                    };
+                    File::create(destination)
+                        .and_then(|file| {
+                            jsonl::with_records(&data.reader, |records| {
+                                export.write(records, BufWriter::new(file))
+                            })?
+                        })
+                        .map(|summary| {
+                            summary.show_report(&export, destination);
+                            summary.rows
+                        })
+                }
+            };
+            match written {
+                Ok(rows) => eprintln!("Converted {} rows", rows),
+                Err(e) => error!("Failed to convert records: {}", e),
+            }
+        }
+        Command::Rows {
+            selection, output, ..
+        } => {
+            let rows = selection.resolve(data.len());
+            let written = open_output(output.as_deref()).and_then(|writer| {
+                jsonl::write_jsonl(writer, rows.filter_map(|row| data.get(row)))
+            });
+            if let Err(e) = written {
+                error!("Failed to write rows: {}", e);
+            }
+        }
+        Command::Sort { .. }
+        | Command::Split { .. }
+        | Command::Sample { .. }
+        | Command::Join(_) => run_stream_command(
+            data.filename(),
+            data.reader
+                .records()
+                .map(|record| record.map(Cow::into_owned).map_err(io::Error::other)),
+            command,
+        ),
+        Command::Group {
+            by,
+            aggregates,
+            jsonl,
+            output,
+        } => {
+            let group_by = match GroupBy::parse(by, aggregates) {
+                Ok(group_by) => group_by,
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+            let result = match jsonl::with_records(&data.reader, |records| group_by.run(records)) {
+                Ok(result) => result,
+                Err(e) => {
+                    error!("Failed to read records: {}", e);
+                    return;
+                }
+            };
+            if *jsonl {
+                let written = open_output(output.as_deref())
+                    .and_then(|writer| jsonl::write_jsonl(writer, result.to_records()));
+                if let Err(e) = written {
+                    error!("Failed to write groups: {}", e);
+                }
+            } else {
+                result.show_table(data.filename());
+            }
+        }
+        Command::Project {
+            fields,
+            flatten,
+            output,
+        } => {
+            let projection = match Projection::parse(fields, *flatten) {
+                Ok(projection) => projection,
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+            let written = open_output(output.as_deref()).and_then(|writer| {
+                jsonl::with_records(&data.reader, |records| {
+                    jsonl::write_jsonl(writer, projection.project(records))
+                })?
+            });
+            if let Err(e) = written {
+                error!("Failed to write projected records: {}", e);
+            }
+        }
+    }
+}
+
+/// Checks every record against the rules file, failing on any violation.
+fn run_validate<R: JsonlReader>(data: &JsonlData<R>, rules: &str) -> ExitCode {
+    let rules = match RuleSet::load(Path::new(rules)) {
+        Ok(rules) => rules,
+        Err(e) => {
+            error!("Failed to load rules: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let report = match jsonl::with_records(&data.reader, |records| rules.evaluate(records)) {
+        Ok(report) => report,
+        Err(e) => {
+            error!("Failed to read records: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    report.show_report(data.filename());
+    if report.passed() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+/// Prints, counts or reports on the records matching a filter expression.
+fn run_filter<R: JsonlReader>(data: &JsonlData<R>, expression: &str, mode: &FilterMode) {
+    let filter = match Filter::parse(expression) {
+        Ok(filter) => filter,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    match mode {
+        FilterMode::Print(output) => {
+            let written = open_output(output.as_deref()).and_then(|writer| {
+                jsonl::with_records(&data.reader, |records| {
+                    jsonl::write_jsonl(writer, records.filter(|record| filter.matches(record)))
+                })?
+            });
+            if let Err(e) = written {
+                error!("Failed to write filtered records: {}", e);
+            }
+        }
+        FilterMode::Count => {
+            match jsonl::with_records(&data.reader, |records| {
+                records.filter(|record| filter.matches(record)).count()
+            }) {
+                Ok(count) => println!("{}", count),
+                Err(e) => error!("Failed to read records: {}", e),
+            }
+        }
+        FilterMode::Report => match data.filtered(&filter) {
+            Ok(subset) => show_reports(&subset),
+            Err(e) => error!("Failed to analyze filtered records: {}", e),
+        },
+    }
+}
+
+/// Joins the input records (left side) with a second JSONL file (right side),
+/// streaming both.
+fn run_join<I>(source_name: &str, records: I, options: &JoinOptions)
+where
+    I: IntoIterator<Item = io::Result<Value>>,
+{
+    let keys = KeyPath::parse_single(&options.left_on).and_then(|left| {
+        let right = options.right_on.as_deref().unwrap_or(&options.left_on);
+        Ok((left, KeyPath::parse_single(right)?))
+    });
+    let (left_key, right_key) = match keys {
+        Ok(keys) => keys,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let right = match jsonl::stream_jsonl_with_progress(
+        Path::new(&options.right),
+        Some(progress::report_to_stderr()),
+    ) {
+        Ok(right) => right,
+        Err(e) => {
+            error!("Failed to read {}: {}", options.right, e);
+            return;
+        }
+    };
+
+    let mut join = Join::new(left_key, right_key);
+    join.kind = options.kind;
+    join.conflicts = options.conflicts.clone();
+    join.strategy = options.strategy;
+
+    let summary =
+        open_output(options.output.as_deref()).and_then(|writer| join.run(records, right, writer));
+    match summary {
+        // Keep the report off stdout when the records are written there
+        Ok(summary) if options.output.is_some() => summary.show_report(source_name, &options.right),
+        Ok(summary) => eprint!("{}", summary.report(source_name, &options.right)),
+        Err(e) => error!("Failed to join: {}", e),
+    }
+}
+
+/// Runs a command that consumes records one at a time, so that file input
+/// can be streamed instead of loaded.
+fn run_stream_command<I>(source_name: &str, records: I, command: &Command)
+where
+    I: IntoIterator<Item = io::Result<Value>>,
+{
+    match command {
+        Command::Sort {
+            by,
+            run_size,
+            output,
+        } => {
+            let spec = match SortSpec::parse(by) {
+                Ok(spec) => spec,
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+            let sorted = open_output(output.as_deref())
+                .and_then(|writer| spec.sort(records, *run_size, writer));
+            match sorted {
+                Ok(summary) => eprintln!(
+                    "Sorted {} rows by {} ({} runs spilled to disk)",
+                    summary.rows, spec, summary.runs
+                ),
+                Err(e) => error!("Failed to sort records: {}", e),
+            }
+        }
+        Command::Split {
+            mode,
+            template,
+            max_open,
+        } => {
+            let mode = match mode {
+                SplitBy::Rows(n) => SplitMode::Rows(*n),
+                SplitBy::Bytes(n) => SplitMode::Bytes(*n),
+                SplitBy::Key(path) => match KeyPath::parse_single(path) {
+                    Ok(path) => SplitMode::Key(path),
+                    Err(e) => {
+                        error!("{}", e);
+                        return;
+                    }
+                },
+            };
+            let mut splitter = Splitter::new(mode);
+            if let Some(template) = template {
+                splitter.template = template.clone();
+            }
+            if let Some(max_open) = max_open {
+                splitter.max_open = *max_open;
+            }
+            match splitter.split(source_name, records) {
+                Ok(summary) => summary.show_report(source_name),
+                Err(e) => error!("Failed to split records: {}", e),
+            }
+        }
+        Command::Sample {
+            size,
+            seed,
+            stratify,
+            report,
+            output,
+        } => {
+            let mut sampler = Sampler::new(*size, seed.unwrap_or_else(random_seed));
+            if let Some(path) = stratify {
+                match KeyPath::parse_single(path) {
+                    Ok(path) => sampler.stratify = Some(path),
+                    Err(e) => {
+                        error!("{}", e);
+                        return;
+                    }
+                }
+            }
+            let sample = match sampler.sample(records) {
+                Ok(sample) => sample,
+                Err(e) => {
+                    error!("Failed to sample records: {}", e);
+                    return;
+                }
+            };
+            let info = sample.info(&sampler);
+            // On stderr, so that the seed of an unseeded sample is always shown
+            eprintln!("Sampled {}", info);
+
+            if *report {
+                let name = format!("{} (sample)", source_name);
+                let rows = sample
+                    .rows
+                    .iter()
+                    .map(|(_, record)| record.clone())
+                    .collect();
+                match JsonlData::new(MemoryJsonlReader::new(name, rows)) {
+                    Ok(data) => {
+                        let data = data.with_sample_info(info);
+                        show_reports(&data);
+                        if sampler.stratify.is_some() {
+                            sample.show_strata_report();
+                        }
+                    }
+                    Err(e) => error!("Failed to analyze sample: {}", e),
+                }
+            } else {
+                let written = open_output(output.as_deref()).and_then(|writer| {
+                    jsonl::write_jsonl(writer, sample.rows.iter().map(|(_, record)| record))
+                });
+                if let Err(e) = written {
+                    error!("Failed to write sample: {}", e);
+                }
+            }
+        }
+        Command::Join(options) => run_join(source_name, records, options),
+        _ => error!("Command {:?} cannot run on a record stream", command),
+    }
+}
+
+/// Follows a growing file until interrupted, printing the key reports
+/// periodically and an alert for every new key path.
+fn run_follow(path: PathBuf, report_interval: Duration, poll_interval: Duration) {
+    let name = path.display().to_string();
+    let mut follower = Follower::new(path);
+    follower.report_interval = report_interval;
+    follower.poll_interval = poll_interval;
+
+    let followed = follower.follow(|event, stats| {
+        match event {
+            FollowEvent::NewKey { path, row } => {
+                println!("ALERT: new key path {} first seen in row {}", path, row)
+            }
+            FollowEvent::Rotated => warn!("{} was rotated, following the new file", name),
+            FollowEvent::Truncated => warn!("{} was truncated, reading from the start", name),
+            FollowEvent::InvalidLine { row, message } => {
+                error!("Invalid JSON after row {}: {}", row, message)
+            }
+            FollowEvent::Report => stats.show_report(&name),
+        }
+        ControlFlow::Continue(())
+    });
+    if let Err(e) = followed {
+        error!("Failed to follow {}: {}", name, e);
+    }
+}
+
+/// Seed for `sample` when none was given; the report shows it so a run can be repeated.
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+/// Writes selected rows of a file without parsing it, using the sidecar line
+/// index when it is up to date (and building it first with `--index`).
+fn run_rows_on_file(
+    path: &Path,
+    selection: &RowSelection,
+    build_index: bool,
+    output: Option<&str>,
+) {
+    let index = if build_index {
+        match LineIndex::load_or_build(path) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                error!("Failed to build line index: {}", e);
+                return;
+            }
+        }
+    } else {
+        // A missing, stale or unreadable sidecar just means reading the file directly
+        LineIndex::load(path).ok().flatten()
+    };
+    let written = open_output(output)
+        .and_then(|writer| index::write_rows(path, selection, index.as_ref(), writer));
+    match written {
+        Ok(rows) => eprintln!("Wrote {} rows", rows),
+        Err(e) => error!("Failed to read rows: {}", e),
+    }
+}
+
+/// Rewrites a file in canonical form line by line. `--check` only compares and
+/// fails when a line would change; `--in-place` replaces the file.
+fn run_fmt_on_file(
+    path: &Path,
+    order: &KeyOrder,
+    ascii: bool,
+    check: bool,
+    in_place: bool,
+    output: Option<&str>,
+) -> ExitCode {
+    // Ranking keys by frequency takes a first, streamed pass over the file,
+    // which refuses invalid lines just as the formatting pass does
+    let canonicalizer = match order {
+        KeyOrder::Frequency => {
+            jsonl::stream_jsonl_with_progress(path, Some(progress::report_to_stderr())).and_then(
+                |records| {
+                    let mut invalid = Vec::new();
+                    let canonicalizer = Canonicalizer::new(
+                        order,
+                        records.filter_map(|record| record.map_err(|e| invalid.push(e)).ok()),
+                    );
+                    match invalid.first() {
+                        Some(first) => Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("{} invalid lines, the first at {}", invalid.len(), first),
+                        )),
+                        None => Ok(canonicalizer),
+                    }
+                },
+            )
+        }
+        _ => Ok(Canonicalizer::new(order, std::iter::empty::<Value>())),
+    };
+
+    let temp_path = path.with_extension("fmt.tmp");
+    let formatted = canonicalizer.and_then(|mut canonicalizer| {
+        canonicalizer.ascii = ascii;
+        let file = File::open(path)?;
+        let input = io::BufReader::new(file);
+        if check {
+            canonicalizer.format_lines(input, None::<io::Sink>)
+        } else if in_place {
+            let summary = canonicalizer
+                .format_lines(input, Some(BufWriter::new(File::create(&temp_path)?)))?;
+            // Leave the file alone rather than silently change its numbers
+            if !summary.lossy_lines.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "numbers on lines {} would change value",
+                        line_list(&summary.lossy_lines)
+                    ),
+                ));
+            }
+            std::fs::rename(&temp_path, path)?;
+            Ok(summary)
+        } else {
+            canonicalizer.format_lines(input, Some(open_output(output)?))
+        }
+    });
+
+    match formatted {
+        Ok(summary) if check && !summary.is_canonical() => {
+            error!(
+                "{} lines are not canonical (lines {})",
+                summary.changed_lines.len(),
+                line_list(&summary.changed_lines)
+            );
+            ExitCode::FAILURE
+        }
+        Ok(summary) => {
+            if !summary.lossy_lines.is_empty() {
+                warn!(
+                    "Numbers on lines {} changed value",
+                    line_list(&summary.lossy_lines)
+                );
+            }
+            eprintln!(
+                "{} rows, {} lines reformatted",
+                summary.rows,
+                summary.changed_lines.len()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&temp_path);
+            error!("Failed to format file: {}", e);
+            if check || in_place {
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+    }
+}
+
+/// The first line numbers of `lines`, comma separated
+fn line_list(lines: &[usize]) -> String {
+    let shown: Vec<String> = lines.iter().take(10).map(|line| line.to_string()).collect();
+    if lines.len() > shown.len() {
+        format!("{}, ...", shown.join(", "))
+    } else {
+        shown.join(", ")
+    }
+}
+
+/// Saves the edit journal of a command when `--journal=path` was given.
+fn save_journal<R: JsonlReader>(data: &JsonlData<R>, path: Option<&str>) {
+    if let Some(path) = path
+        && let Err(e) = data.journal.save(path)
+    {
+        error!("Failed to save journal {}: {}", path, e);
+    }
 }
 
 /// Process the file based on the provided filename using FileJsonlReader.
 pub fn process_file(filename: String) {
+    process_file_with_command(filename, &Command::Report);
+}
+
+/// Like [`process_file`], running `command` instead of the key reports and
+/// returning the exit status of the command. The file is parsed on
+/// [`parallel::default_threads`] threads, as on the command line.
+pub fn process_file_with_command(filename: String, command: &Command) -> ExitCode {
+    process_file_with_threads(filename, command, parallel::default_threads())
+}
+
+/// Like [`process_file_with_command`], parsing and analyzing the file on `threads` threads.
+pub fn process_file_with_threads(filename: String, command: &Command, threads: usize) -> ExitCode {
     let span = span!(Level::INFO, "process_file", filename = filename);
     let _ = span.enter();
 
-    let path = PathBuf::from(filename);
-    let reader = FileJsonlReader::new(path);
+    let path = PathBuf::from(&filename);
+
+    if let Command::Rows {
+        selection,
+        index,
+        output,
+    } = command
+    {
+        run_rows_on_file(&path, selection, *index, output.as_deref());
+        return ExitCode::SUCCESS;
+    }
+    if let Command::Follow {
+        report_interval,
+        poll_interval,
+    } = command
+    {
+        run_follow(path, *report_interval, *poll_interval);
+        return ExitCode::SUCCESS;
+    }
+    if let Command::Fmt {
+        order,
+        ascii,
+        check,
+        in_place,
+        output,
+    } = command
+    {
+        return run_fmt_on_file(&path, order, *ascii, *check, *in_place, output.as_deref());
+    }
 
-    match JsonlData::new(reader) {
+    // These commands stream the file, so it is never loaded into memory as a whole
+    if command.is_streaming() {
+        return match jsonl::stream_jsonl_with_progress(&path, Some(progress::report_to_stderr())) {
+            Ok(records) => {
+                run_stream_command(&filename, records, command);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                error!("Failed to process file: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let reader = FileJsonlReader::new(path).with_threads(threads);
+
+    match JsonlData::with_progress(reader, threads, Some(progress::report_to_stderr())) {
+        Ok(data) => run_command(data, command),
+        Err(e) => {
+            error!("Failed to process file: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Process the file through a memory map using MmapJsonlReader, so records
+/// are only parsed when a command needs them.
+pub fn process_mapped_file(filename: String, command: &Command, threads: usize) -> ExitCode {
+    let span = span!(Level::INFO, "process_mapped_file", filename = filename);
+    let _ = span.enter();
+
+    // Row selection and streaming commands never load the file anyway
+    if command.is_streaming() || matches!(command, Command::Rows { .. } | Command::Fmt { .. }) {
+        return process_file_with_threads(filename, command, threads);
+    }
+
+    let reader = MmapJsonlReader::new(PathBuf::from(&filename));
+    match JsonlData::with_progress(reader, threads, Some(progress::report_to_stderr())) {
+        Ok(data) => run_command(data, command),
+        Err(e) => {
+            error!("Failed to process file: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Process a CSV or TSV file using CsvJsonlReader; the key reports are
+/// preceded by the inferred column types.
+pub fn process_table_file(filename: String, options: CsvOptions, command: &Command) -> ExitCode {
+    let span = span!(Level::INFO, "process_table_file", filename = filename);
+    let _ = span.enter();
+
+    let reader = CsvJsonlReader::new(PathBuf::from(&filename), options);
+    match JsonlData::with_progress(reader, 1, Some(progress::report_to_stderr())) {
+        Ok(data) => {
+            if *command == Command::Report {
+                data.reader.show_schema_report();
+            }
+            run_command(data, command)
+        }
+        Err(e) => {
+            error!("Failed to process file: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Process a JSON array file or concatenated JSON documents using
+/// JsonDocumentReader. Converting to JSONL streams the records straight to
+/// the output without loading them.
+pub fn process_json_file(filename: String, command: &Command) -> ExitCode {
+    let span = span!(Level::INFO, "process_json_file", filename = filename);
+    let _ = span.enter();
+
+    if let Command::Convert {
+        to: ConvertTo::Jsonl,
+        output,
+        ..
+    } = command
+    {
+        let converted = File::open(&filename).and_then(|file| {
+            let mut writer = open_output(output.as_deref())?;
+            let (layout, rows) = documents::read_json_documents(file, |record| {
+                serde_json::to_writer(&mut writer, &record)?;
+                writer.write_all(b"\n")
+            })?;
+            writer.flush()?;
+            Ok((layout, rows))
+        });
+        return match converted {
+            Ok((layout, rows)) => {
+                eprintln!("Converted {} rows from {}", rows, layout);
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                error!("Failed to convert records: {}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    let reader = JsonDocumentReader::new(PathBuf::from(&filename));
+    match JsonlData::with_progress(reader, 1, Some(progress::report_to_stderr())) {
         Ok(data) => {
-            data.show_keys_found_report();
-            data.show_keys_frequencies_report();
-            data.show_top_key_combinations_report(5);
-            // Example of showing a specific record (optional)
-            // let record_id = 10;
-            // data.show_record(record_id);
+            if let Some(layout) = data.reader.layout() {
+                eprintln!("Read {} rows from {}", data.len(), layout);
+            }
+            run_command(data, command)
         }
         Err(e) => {
             error!("Failed to process file: {}", e);
+            ExitCode::FAILURE
         }
     }
 }
 
 /// Process JSONL data from a URL using HttpJsonlReader.
 pub fn process_url(url: String) {
+    process_url_with_command(url, &Command::Report);
+}
+
+/// Like [`process_url`], running `command` instead of the key reports and
+/// returning the exit status of the command.
+pub fn process_url_with_command(url: String, command: &Command) -> ExitCode {
     let span = span!(Level::INFO, "process_url", url = url);
     let _ = span.enter();
 
     let reader = HttpJsonlReader::new(url);
 
-    match JsonlData::new(reader) {
-        Ok(data) => {
-            data.show_keys_found_report();
-            data.show_keys_frequencies_report();
-            data.show_top_key_combinations_report(5);
-        }
+    match JsonlData::with_progress(reader, 1, Some(progress::report_to_stderr())) {
+        Ok(data) => run_command(data, command),
         Err(e) => {
             error!("Failed to process URL: {}", e);
+            ExitCode::FAILURE
         }
     }
 }
@@ -68,22 +1030,30 @@ pub fn process_url(url: String) {
 /// Process JSONL data from memory using MemoryJsonlReader.
 /// This is useful for testing or when you already have the data in memory.
 pub fn process_memory_data(name: String, json_lines: Vec<&str>) {
+    process_memory_data_with_command(name, json_lines, &Command::Report);
+}
+
+/// Like [`process_memory_data`], running `command` instead of the key reports
+/// and returning the exit status of the command.
+pub fn process_memory_data_with_command(
+    name: String,
+    json_lines: Vec<&str>,
+    command: &Command,
+) -> ExitCode {
     let span = span!(Level::INFO, "process_memory_data", name = name);
     let _ = span.enter();
 
     match MemoryJsonlReader::from_strings(name, json_lines) {
         Ok(reader) => match JsonlData::new(reader) {
-            Ok(data) => {
-                data.show_keys_found_report();
-                data.show_keys_frequencies_report();
-                data.show_top_key_combinations_report(5);
-            }
+            Ok(data) => run_command(data, command),
             Err(e) => {
                 error!("Failed to process memory data: {}", e);
+                ExitCode::FAILURE
             }
         },
         Err(e) => {
             error!("Failed to parse JSON lines: {}", e);
+            ExitCode::FAILURE
         }
     }
 }
@@ -92,10 +1062,177 @@ pub fn process_memory_data(name: String, json_lines: Vec<&str>) {
 #[derive(Debug)]
 pub enum DataSource {
     File(String),
+    /// A file read through a memory map (`--mmap`), parsing records on demand
+    MappedFile(String),
+    /// A CSV or TSV file with a header row (`--csv=path`, `--tsv=path`)
+    Table(String, CsvOptions),
+    /// A JSON array or concatenated JSON documents (`--json=path`)
+    Json(String),
     Url(String),
     Memory(String, Vec<String>), // name and json lines
 }
 
+/// Operation to run on the data source. Selected by the first argument that
+/// does not start with `--`; without one the key reports are shown.
+#[derive(Debug, Default, PartialEq)]
+pub enum Command {
+    #[default]
+    Report,
+    /// `transform --rule=SPEC... [--dry-run] [--output=path] [--journal=path]`
+    Transform {
+        rules: Vec<String>,
+        dry_run: bool,
+        output: Option<String>,
+        journal: Option<String>,
+    },
+    /// `convert --to=jsonl|csv|tsv [--columns=PATH,...] [--output=path]`
+    Convert {
+        to: ConvertTo,
+        /// Column order given by the user; by default all key paths by frequency
+        columns: Option<Vec<String>>,
+        output: Option<String>,
+    },
+    /// `duplicates [--key=PATH]`
+    Duplicates { key: Option<String> },
+    /// `profile`: string lengths, padding and value patterns per key
+    Profile,
+    /// `timestamps [--key=PATH] [--gap=1h] [--bucket=minute|hour|day]`; without
+    /// `--key` every key holding timestamps is reported
+    Timestamps {
+        key: Option<String>,
+        gap: chrono::TimeDelta,
+        bucket: Option<Bucket>,
+    },
+    /// `validate --rules=path.(json|toml)`; exits with status 1 when a rule fails
+    Validate { rules: String },
+    /// `missing-keys [--optional=PATH,...] [--null-as-missing] [--report] [--output=path]`:
+    /// one JSONL line per row lacking some required key path
+    MissingKeys {
+        completeness: Completeness,
+        report: bool,
+        output: Option<String>,
+    },
+    /// `dedup [--key=PATH] [--keep=first|last|most-keys] [--output=path]`
+    Dedup {
+        key: Option<String>,
+        keep: KeepPolicy,
+        output: Option<String>,
+    },
+    /// `filter --where=EXPR [--count | --report | --output=path]`
+    Filter {
+        expression: String,
+        mode: FilterMode,
+    },
+    /// `group --by=PATH,... [--agg=count,avg(path),...] [--jsonl [--output=path]]`
+    Group {
+        by: Vec<String>,
+        aggregates: String,
+        jsonl: bool,
+        output: Option<String>,
+    },
+    /// `join --right=path --on=PATH [--right-on=PATH] [--type=inner|left|full]
+    /// [--conflict=prefix|left-wins|right-wins] [--strategy=hash|sort-merge]
+    /// [--run-size=N] [--output=path]`
+    Join(JoinOptions),
+    /// `project --fields=PATH,... [--flatten] [--output=path]`
+    Project {
+        fields: Vec<String>,
+        flatten: bool,
+        output: Option<String>,
+    },
+    /// `sort --by="PATH [asc|desc], ..." [--run-size=N] [--output=path]`
+    Sort {
+        by: String,
+        run_size: usize,
+        output: Option<String>,
+    },
+    /// `split --rows=N | --bytes=N | --by=PATH [--template=...] [--max-open=N]`
+    Split {
+        mode: SplitBy,
+        template: Option<String>,
+        max_open: Option<usize>,
+    },
+    /// `sample --n=N | --fraction=P [--seed=N] [--stratify=PATH] [--report | --output=path]`
+    Sample {
+        size: SampleSize,
+        seed: Option<u64>,
+        stratify: Option<String>,
+        report: bool,
+        output: Option<String>,
+    },
+    /// `head [--n=10]`, `tail [--n=10]` or `slice --rows=A..B`, with
+    /// `[--index] [--output=path]`; `--index` builds the sidecar line index
+    Rows {
+        selection: RowSelection,
+        index: bool,
+        output: Option<String>,
+    },
+    /// `follow [--report-every=SECS] [--poll-ms=MS]`, files only; runs until interrupted
+    Follow {
+        report_interval: Duration,
+        poll_interval: Duration,
+    },
+    /// `fmt [--order=sorted|frequency|KEY,...] [--ascii] [--check | --in-place | --output=path]`
+    Fmt {
+        order: KeyOrder,
+        ascii: bool,
+        check: bool,
+        in_place: bool,
+        output: Option<String>,
+    },
+    /// `replay --journal=path [--force] [--output=path]`
+    Replay {
+        journal: String,
+        force: bool,
+        output: Option<String>,
+    },
+}
+
+/// What the `filter` command does with the matching records
+#[derive(Debug, PartialEq)]
+pub enum FilterMode {
+    /// Write them as JSONL to the given file, or stdout
+    Print(Option<String>),
+    Count,
+    /// Run the key reports on them
+    Report,
+}
+
+/// How the `split` command divides records (`--rows`, `--bytes` or `--by`)
+#[derive(Debug, PartialEq)]
+pub enum SplitBy {
+    Rows(usize),
+    Bytes(u64),
+    Key(String),
+}
+
+/// Options of the `join` command
+#[derive(Debug, PartialEq)]
+pub struct JoinOptions {
+    /// Path of the right-hand JSONL file
+    pub right: String,
+    pub left_on: String,
+    /// Key path on the right side, when it differs from `left_on`
+    pub right_on: Option<String>,
+    pub kind: JoinKind,
+    pub conflicts: ConflictPolicy,
+    pub strategy: JoinStrategy,
+    pub output: Option<String>,
+}
+
+impl Command {
+    /// Commands that can consume file input as a stream of records
+    pub fn is_streaming(&self) -> bool {
+        matches!(
+            self,
+            Command::Sort { .. }
+                | Command::Split { .. }
+                | Command::Sample { .. }
+                | Command::Join(_)
+        )
+    }
+}
+
 /// Parses command-line arguments to determine the data source.
 /// Returns `Some(DataSource)` if a valid source is provided, otherwise `None`.
 pub fn parse_cli_arguments() -> Option<DataSource> {
@@ -106,39 +1243,373 @@ pub fn parse_cli_arguments() -> Option<DataSource> {
 
     for arg in &args {
         if let Some(val) = arg.strip_prefix("--filename=") {
+            if has_flag(&args, "mmap") {
+                return Some(DataSource::MappedFile(val.to_string()));
+            }
             return Some(DataSource::File(val.to_string()));
         }
         if let Some(val) = arg.strip_prefix("--url=") {
             return Some(DataSource::Url(val.to_string()));
         }
+        if let Some(val) = arg.strip_prefix("--json=") {
+            return Some(DataSource::Json(val.to_string()));
+        }
+        for (prefix, format) in [("--csv=", TableFormat::Csv), ("--tsv=", TableFormat::Tsv)] {
+            if let Some(val) = arg.strip_prefix(prefix) {
+                let mut options = CsvOptions::new(format);
+                options.nested = has_flag(&args, "nested");
+                options.empty_as_null = has_flag(&args, "empty-as-null");
+                options.literal_null = has_flag(&args, "literal-null");
+                return Some(DataSource::Table(val.to_string(), options));
+            }
+        }
     }
 
     None
 }
 
-/// Main entry point that handles different data sources
-pub fn run() {
+/// Returns every value given for `--name=value` (options may repeat).
+fn option_values<'a>(args: &'a [String], name: &str) -> Vec<&'a str> {
+    let prefix = format!("--{}=", name);
+    args.iter()
+        .filter_map(|arg| arg.strip_prefix(prefix.as_str()))
+        .collect()
+}
+
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    option_values(args, name).pop()
+}
+
+fn has_flag(args: &[String], name: &str) -> bool {
+    let flag = format!("--{}", name);
+    args.contains(&flag)
+}
+
+/// Records held in memory per sorted run (`--run-size=N`)
+fn parse_run_size(args: &[String]) -> Result<usize, String> {
+    option_value(args, "run-size")
+        .map(|n| n.parse().map_err(|_| format!("Invalid --run-size: {}", n)))
+        .transpose()
+        .map(|n| n.unwrap_or(100_000))
+}
+
+/// Worker threads for parsing and analysis (`--threads=N`), defaulting to
+/// [`parallel::default_threads`] like the library entry points
+fn parse_threads(args: &[String]) -> Result<usize, String> {
+    match option_value(args, "threads") {
+        Some(n) => match n.parse() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("Invalid --threads: {}", n)),
+        },
+        None => Ok(parallel::default_threads()),
+    }
+}
+
+/// Parses the command and its options from the arguments (without the program name).
+pub fn parse_command(args: &[String]) -> Result<Command, String> {
+    let name = args.iter().find(|arg| !arg.starts_with("--"));
+
+    match name.map(String::as_str) {
+        None | Some("report") => Ok(Command::Report),
+        Some("transform") => {
+            let rules: Vec<String> = option_values(args, "rule")
+                .into_iter()
+                .map(String::from)
+                .collect();
+            if rules.is_empty() {
+                return Err("transform requires at least one --rule=SPEC".to_string());
+            }
+            Ok(Command::Transform {
+                rules,
+                dry_run: has_flag(args, "dry-run"),
+                output: option_value(args, "output").map(String::from),
+                journal: option_value(args, "journal").map(String::from),
+            })
+        }
+        Some("convert") => {
+            let to =
+                option_value(args, "to").ok_or("convert requires --to=jsonl|csv|tsv|parquet")?;
+            let to = ConvertTo::parse(to).ok_or(format!("Unknown output format: {}", to))?;
+            if to == ConvertTo::Parquet && option_value(args, "output").is_none() {
+                return Err("convert --to=parquet requires --output=FILE".to_string());
+            }
+            Ok(Command::Convert {
+                to,
+                columns: option_value(args, "columns")
+                    .map(|list| list.split(',').map(String::from).collect()),
+                output: option_value(args, "output").map(String::from),
+            })
+        }
+        Some("missing-keys") => Ok(Command::MissingKeys {
+            completeness: Completeness::new(
+                option_values(args, "optional")
+                    .into_iter()
+                    .flat_map(|list| list.split(','))
+                    .map(String::from),
+                has_flag(args, "null-as-missing"),
+            ),
+            report: has_flag(args, "report"),
+            output: option_value(args, "output").map(String::from),
+        }),
+        Some("profile") => Ok(Command::Profile),
+        Some("timestamps") => Ok(Command::Timestamps {
+            key: option_value(args, "key").map(String::from),
+            gap: match option_value(args, "gap") {
+                Some(gap) => timestamps::parse_duration(gap).ok_or(format!(
+                    "Invalid --gap (expected e.g. 90s, 5m, 1h, 2d): {}",
+                    gap
+                ))?,
+                None => chrono::TimeDelta::hours(1),
+            },
+            bucket: option_value(args, "bucket")
+                .map(|name| Bucket::parse(name).ok_or(format!("Unknown bucket: {}", name)))
+                .transpose()?,
+        }),
+        Some("validate") => Ok(Command::Validate {
+            rules: option_value(args, "rules")
+                .ok_or("validate requires --rules=path")?
+                .to_string(),
+        }),
+        Some("duplicates") => Ok(Command::Duplicates {
+            key: option_value(args, "key").map(String::from),
+        }),
+        Some("dedup") => Ok(Command::Dedup {
+            key: option_value(args, "key").map(String::from),
+            keep: match option_value(args, "keep") {
+                Some(name) => {
+                    KeepPolicy::parse(name).ok_or(format!("Unknown keep policy: {}", name))?
+                }
+                None => KeepPolicy::default(),
+            },
+            output: option_value(args, "output").map(String::from),
+        }),
+        Some("filter") => {
+            let expression = option_value(args, "where").ok_or("filter requires --where=EXPR")?;
+            let mode = if has_flag(args, "count") {
+                FilterMode::Count
+            } else if has_flag(args, "report") {
+                FilterMode::Report
+            } else {
+                FilterMode::Print(option_value(args, "output").map(String::from))
+            };
+            Ok(Command::Filter {
+                expression: expression.to_string(),
+                mode,
+            })
+        }
+        Some("group") => Ok(Command::Group {
+            by: option_value(args, "by")
+                .map(|by| by.split(',').map(String::from).collect())
+                .unwrap_or_default(),
+            aggregates: option_value(args, "agg").unwrap_or("count").to_string(),
+            jsonl: has_flag(args, "jsonl"),
+            output: option_value(args, "output").map(String::from),
+        }),
+        Some("join") => {
+            let right = option_value(args, "right").ok_or("join requires --right=path")?;
+            let left_on = option_value(args, "on").ok_or("join requires --on=PATH")?;
+            let kind = match option_value(args, "type") {
+                Some(name) => {
+                    JoinKind::parse(name).ok_or(format!("Unknown join type: {}", name))?
+                }
+                None => JoinKind::default(),
+            };
+            let conflicts = match option_value(args, "conflict") {
+                Some(name) => ConflictPolicy::parse(name)
+                    .ok_or(format!("Unknown conflict policy: {}", name))?,
+                None => ConflictPolicy::default(),
+            };
+            let strategy = match option_value(args, "strategy") {
+                None | Some("hash") => JoinStrategy::Hash,
+                Some("sort-merge") => JoinStrategy::SortMerge {
+                    run_size: parse_run_size(args)?,
+                },
+                Some(other) => return Err(format!("Unknown join strategy: {}", other)),
+            };
+            Ok(Command::Join(JoinOptions {
+                right: right.to_string(),
+                left_on: left_on.to_string(),
+                right_on: option_value(args, "right-on").map(String::from),
+                kind,
+                conflicts,
+                strategy,
+                output: option_value(args, "output").map(String::from),
+            }))
+        }
+        Some("project") => {
+            let fields: Vec<String> = option_values(args, "fields")
+                .into_iter()
+                .flat_map(|list| list.split(','))
+                .chain(option_values(args, "field"))
+                .filter(|f| !f.trim().is_empty())
+                .map(String::from)
+                .collect();
+            if fields.is_empty() {
+                return Err("project requires --fields=PATH,...".to_string());
+            }
+            Ok(Command::Project {
+                fields,
+                flatten: has_flag(args, "flatten"),
+                output: option_value(args, "output").map(String::from),
+            })
+        }
+        Some("sort") => Ok(Command::Sort {
+            by: option_value(args, "by")
+                .ok_or("sort requires --by=PATH")?
+                .to_string(),
+            run_size: parse_run_size(args)?,
+            output: option_value(args, "output").map(String::from),
+        }),
+        Some("split") => {
+            let number = |name: &str| {
+                option_value(args, name)
+                    .map(|n| {
+                        n.parse::<u64>()
+                            .map_err(|_| format!("Invalid --{}: {}", name, n))
+                    })
+                    .transpose()
+            };
+            let mode = if let Some(rows) = number("rows")? {
+                SplitBy::Rows(rows as usize)
+            } else if let Some(bytes) = number("bytes")? {
+                SplitBy::Bytes(bytes)
+            } else if let Some(path) = option_value(args, "by") {
+                SplitBy::Key(path.to_string())
+            } else {
+                return Err("split requires --rows=N, --bytes=N or --by=PATH".to_string());
+            };
+            Ok(Command::Split {
+                mode,
+                template: option_value(args, "template").map(String::from),
+                max_open: number("max-open")?.map(|n| n as usize),
+            })
+        }
+        Some("sample") => {
+            let size = match (option_value(args, "n"), option_value(args, "fraction")) {
+                (Some(n), None) => {
+                    SampleSize::Count(n.parse().map_err(|_| format!("Invalid --n: {}", n))?)
+                }
+                (None, Some(p)) => match p.parse::<f64>() {
+                    Ok(p) if (0.0..=1.0).contains(&p) => SampleSize::Fraction(p),
+                    _ => return Err(format!("Invalid --fraction (expected 0..1): {}", p)),
+                },
+                _ => return Err("sample requires either --n=N or --fraction=P".to_string()),
+            };
+            let seed = option_value(args, "seed")
+                .map(|n| n.parse().map_err(|_| format!("Invalid --seed: {}", n)))
+                .transpose()?;
+            Ok(Command::Sample {
+                size,
+                seed,
+                stratify: option_value(args, "stratify").map(String::from),
+                report: has_flag(args, "report"),
+                output: option_value(args, "output").map(String::from),
+            })
+        }
+        Some(name @ ("head" | "tail" | "slice")) => {
+            let n = option_value(args, "n")
+                .map(|n| n.parse().map_err(|_| format!("Invalid --n: {}", n)))
+                .transpose()?
+                .unwrap_or(10);
+            let selection = match name {
+                "head" => RowSelection::Head(n),
+                "tail" => RowSelection::Tail(n),
+                _ => {
+                    let spec = option_value(args, "rows").ok_or("slice requires --rows=A..B")?;
+                    RowSelection::parse_range(spec)
+                        .ok_or(format!("Invalid --rows (expected A..B): {}", spec))?
+                }
+            };
+            Ok(Command::Rows {
+                selection,
+                index: has_flag(args, "index"),
+                output: option_value(args, "output").map(String::from),
+            })
+        }
+        Some("follow") => {
+            let number = |name: &str, default: u64| {
+                option_value(args, name)
+                    .map(|n| n.parse().map_err(|_| format!("Invalid --{}: {}", name, n)))
+                    .transpose()
+                    .map(|n| n.unwrap_or(default))
+            };
+            Ok(Command::Follow {
+                report_interval: Duration::from_secs(number("report-every", 10)?),
+                poll_interval: Duration::from_millis(number("poll-ms", 500)?),
+            })
+        }
+        Some("fmt") => Ok(Command::Fmt {
+            order: option_value(args, "order").map_or(KeyOrder::Sorted, KeyOrder::parse),
+            ascii: has_flag(args, "ascii"),
+            check: has_flag(args, "check"),
+            in_place: has_flag(args, "in-place"),
+            output: option_value(args, "output").map(String::from),
+        }),
+        Some("replay") => Ok(Command::Replay {
+            journal: option_value(args, "journal")
+                .ok_or("replay requires --journal=path")?
+                .to_string(),
+            force: has_flag(args, "force"),
+            output: option_value(args, "output").map(String::from),
+        }),
+        Some(other) => Err(format!("Unknown command: {}", other)),
+    }
+}
+
+/// Main entry point that handles different data sources, returning the exit
+/// status for `main`.
+pub fn run() -> ExitCode {
     // Initialize logging
     init_tracing();
-    print_welcome();
-    
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = match parse_command(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let threads = match parse_threads(&args) {
+        Ok(threads) => threads,
+        Err(e) => {
+            error!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+
     // Parse the arguments to determine data source
     match parse_cli_arguments() {
         Some(DataSource::File(filename)) => {
             info!("Processing file: {}", filename);
-            process_file(filename);
+            process_file_with_threads(filename, &command, threads)
+        }
+        Some(DataSource::MappedFile(filename)) => {
+            info!("Processing memory-mapped file: {}", filename);
+            process_mapped_file(filename, &command, threads)
+        }
+        Some(DataSource::Table(filename, options)) => {
+            info!("Processing table file: {}", filename);
+            process_table_file(filename, options, &command)
+        }
+        Some(DataSource::Json(filename)) => {
+            info!("Processing JSON file: {}", filename);
+            process_json_file(filename, &command)
         }
         Some(DataSource::Url(url)) => {
             info!("Processing URL: {}", url);
-            process_url(url);
+            process_url_with_command(url, &command)
         }
         Some(DataSource::Memory(name, lines)) => {
             info!("Processing memory data: {}", name);
             let line_refs: Vec<&str> = lines.iter().map(|s| s.as_str()).collect();
-            process_memory_data(name, line_refs);
+            process_memory_data_with_command(name, line_refs, &command)
         }
         None => {
-            error!("No valid data source provided. Use --filename=<path> or --url=<url>");
+            error!(
+                "No valid data source provided. Use --filename=<path>, --json=<path>, --csv=<path>, --tsv=<path> or --url=<url>"
+            );
+            ExitCode::FAILURE
         }
     }
 }
@@ -146,7 +1617,8 @@ pub fn run() {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json::json;
+    use serde_json::{Value, json};
+    use transform::RuleOutcome;
 
     #[test]
     fn test_example() {
@@ -232,4 +1704,1698 @@ mod tests {
         memory_data.show_keys_found_report();
         memory_data.show_keys_frequencies_report();
     }
+
+    #[test]
+    fn test_transform_rename_move_delete() {
+        let json_lines = vec![
+            r#"{"user_name": "Alice", "debug": {"trace": 1, "span": 2}, "meta": {"ts": 1}}"#,
+            r#"{"user_name": "Bob", "meta": {"ts": 2}}"#,
+            r#"{"id": 3, "debug": {"trace": 3}}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("test".to_string(), json_lines).unwrap();
+        let mut data = JsonlData::new(reader).unwrap();
+
+        let transform = Transform::parse(&[
+            "move:user_name=user.name",
+            "delete:debug.*",
+            "rename:meta.ts=timestamp",
+        ])
+        .unwrap();
+
+        let dry_run = data.apply_transform(&transform, true).unwrap();
+        assert_eq!(dry_run.rows_touched, vec![2, 2, 2]);
+        assert_eq!(dry_run.rows_changed, 3);
+        assert_eq!(data.get(0).unwrap()["user_name"], "Alice");
+
+        data.apply_transform(&transform, false).unwrap();
+        assert_eq!(
+            data.get(0).unwrap(),
+            &json!({"user": {"name": "Alice"}, "meta": {"timestamp": 1}})
+        );
+        assert_eq!(data.get(2).unwrap(), &json!({"id": 3}));
+        assert!(!data.keys_seen.as_ref().unwrap().contains("debug"));
+
+        // Only objects emptied by the deletion are pruned
+        let mut record = json!({"a": {"b": {"c": 1}}, "e": {}, "l": [{"x": 1}]});
+        Transform::parse(&["delete:a.b.c", "delete:l[*].x"])
+            .unwrap()
+            .apply(&mut record);
+        assert_eq!(record, json!({"e": {}, "l": [{}]}));
+        assert!(data.keys_seen.as_ref().unwrap().contains("user.name"));
+    }
+
+    #[test]
+    fn test_report_paths_share_one_array_notation() {
+        let record = json!({"id": 1, "items": [{"id": 10}, {"id": 11, "qty": 2}], "tags": []});
+        let mut visited = Vec::new();
+        path::walk_paths(&record, &mut |path, _| visited.push(path.to_string()));
+        assert_eq!(
+            visited,
+            vec![
+                "id",
+                "items",
+                "items[]",
+                "items[].id",
+                "items[]",
+                "items[].id",
+                "items[].qty",
+                "tags"
+            ]
+        );
+
+        // The key report keeps concrete indices
+        let reader = MemoryJsonlReader::new(
+            "paths".to_string(),
+            vec![record.clone(), json!({"items": [{"id": 12}]})],
+        );
+        let data = JsonlData::new(reader).unwrap();
+        let freqs: std::collections::HashMap<String, usize> =
+            data.key_freqs.clone().unwrap().into_iter().collect();
+        assert_eq!(freqs["items[0].id"], 2);
+        assert_eq!(freqs["items[1].qty"], 1);
+        assert!(!freqs.contains_key("items[].id"));
+
+        // A report path works as a pattern over the elements
+        let ids = KeyPath::parse("items[].id").unwrap();
+        let found: Vec<&Value> = ids
+            .expand(&record)
+            .iter()
+            .filter_map(|(concrete, _)| concrete.get(&record))
+            .collect();
+        assert_eq!(found, vec![&json!(10), &json!(11)]);
+        assert_eq!(ids.to_string(), "items[].id");
+        assert_eq!(KeyPath::parse("debug.*").unwrap().to_string(), "debug[]");
+
+        // Keys taking one value per record refuse a wildcard instead of missing
+        assert!(KeyPath::parse_single("items[].id").is_err());
+        assert!(KeyPath::parse_single("items[1].id").is_ok());
+        assert!(SortSpec::parse("tags[] desc").is_err());
+        assert!(GroupBy::parse(&["tags[]"], "count").is_err());
+    }
+
+    #[test]
+    fn test_transform_wildcard_move() {
+        let mut record = json!({"debug": {"a": 1, "b": [1, 2]}, "keep": true});
+        let transform = Transform::parse(&["move:debug.*=meta.debug_*"]).unwrap();
+        assert_eq!(
+            transform.apply(&mut record),
+            vec![RuleOutcome {
+                changed: 2,
+                collisions: 0
+            }]
+        );
+        // Like delete, a move removes the objects it leaves empty
+        assert_eq!(
+            record,
+            json!({"meta": {"debug_a": 1, "debug_b": [1, 2]}, "keep": true})
+        );
+
+        // An existing target is kept and the source left in place
+        let mut record = json!({"name": "Alice", "user": {"name": "Bob"}, "a": "x", "b": "y"});
+        let transform = Transform::parse(&["move:name=user.name", "rename:a=b"]).unwrap();
+        let outcomes = transform.apply(&mut record);
+        assert_eq!(
+            record,
+            json!({"name": "Alice", "user": {"name": "Bob"}, "a": "x", "b": "y"})
+        );
+        assert!(outcomes.iter().all(|o| o.changed == 0 && o.collisions == 1));
+
+        let reader = MemoryJsonlReader::new(
+            "collide".to_string(),
+            vec![record, json!({"name": "Carol"})],
+        );
+        let mut data = JsonlData::new(reader).unwrap();
+        let summary = data.apply_transform(&transform, true).unwrap();
+        assert_eq!(summary.rows_touched, vec![1, 0]);
+        assert_eq!(summary.collisions, vec![1, 1]);
+
+        assert!(Transform::parse(&["rename:a=b.c"]).is_err());
+        assert!(Transform::parse(&["move:a=*.b"]).is_err());
+        assert!(Transform::parse(&["copy:a=b"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_transform_command() {
+        let args: Vec<String> = [
+            "--filename=x.jsonl",
+            "transform",
+            "--rule=delete:a",
+            "--dry-run",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert_eq!(
+            parse_command(&args).unwrap(),
+            Command::Transform {
+                rules: vec!["delete:a".to_string()],
+                dry_run: true,
+                output: None,
+                journal: None,
+            }
+        );
+        assert_eq!(parse_command(&[]).unwrap(), Command::Report);
+        assert!(parse_command(&["bogus".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_edit_journal_undo_redo() {
+        let json_lines = vec![r#"{"name": "Alice"}"#, r#"{"name": "Bob"}"#];
+        let reader = MemoryJsonlReader::from_strings("test".to_string(), json_lines).unwrap();
+        let mut data = JsonlData::new(reader).unwrap();
+
+        data.replace_record(0, json!({"name": "Alicia"})).unwrap();
+        data.replace_record(1, json!({"name": "Bob", "age": 25}))
+            .unwrap();
+        assert_eq!(data.journal.len(), 2);
+        assert_eq!(data.journal.entries()[0].old, json!({"name": "Alice"}));
+
+        assert_eq!(data.undo().unwrap(), Some(1));
+        assert_eq!(data.get(1).unwrap(), &json!({"name": "Bob"}));
+        assert!(!data.keys_seen.as_ref().unwrap().contains("age"));
+
+        assert_eq!(data.redo().unwrap(), Some(1));
+        assert_eq!(data.get(1).unwrap()["age"], 25);
+        assert_eq!(data.redo().unwrap(), None);
+
+        // A new edit after an undo discards the redo history
+        data.undo().unwrap();
+        data.replace_record(1, json!({"name": "Robert"})).unwrap();
+        assert!(!data.journal.can_redo());
+        assert_eq!(data.journal.len(), 2);
+    }
+
+    #[test]
+    fn test_edit_journal_save_and_replay() {
+        let json_lines = vec![r#"{"id": 1, "status": "ok"}"#, r#"{"id": 2}"#];
+        let reader = MemoryJsonlReader::from_strings("a".to_string(), json_lines.clone()).unwrap();
+        let mut original = JsonlData::new(reader).unwrap();
+        original
+            .replace_record(1, json!({"id": 2, "status": "fixed"}))
+            .unwrap();
+
+        let mut saved = Vec::new();
+        original.journal.write(&mut saved).unwrap();
+        let journal = EditJournal::read(saved.as_slice()).unwrap();
+        assert_eq!(journal.entries(), original.journal.entries());
+
+        let reader = MemoryJsonlReader::from_strings("b".to_string(), json_lines).unwrap();
+        let mut copy = JsonlData::new(reader).unwrap();
+        let summary = copy.replay_journal(&journal, false).unwrap();
+        assert_eq!(summary.applied, 1);
+        assert_eq!(copy.get(1).unwrap()["status"], "fixed");
+        assert_eq!(copy.journal.len(), 1);
+
+        // Replaying again conflicts, since row 1 no longer holds the old value
+        let summary = copy.replay_journal(&journal, false).unwrap();
+        assert_eq!(summary.conflicts, vec![1]);
+
+        // A row out of range rolls back the whole replay
+        let mut bad = EditJournal::new();
+        bad.record(journal::JournalEntry::new(
+            0,
+            json!({"id": 1, "status": "ok"}),
+            json!({"id": 1}),
+        ));
+        bad.record(journal::JournalEntry::new(5, json!({}), json!({"id": 6})));
+        assert!(copy.replay_journal(&bad, true).is_err());
+        assert_eq!(copy.get(0).unwrap()["status"], "ok");
+        assert_eq!(copy.journal.len(), 1);
+    }
+
+    #[test]
+    fn test_filter_expressions() {
+        let json_lines = vec![
+            r#"{"name": "Alice", "age": 30, "status": "ok", "tags": ["a"]}"#,
+            r#"{"name": "Andy", "age": 45, "status": "error"}"#,
+            r#"{"name": "Bob", "age": 35, "status": "error", "gender": null}"#,
+            r#"{"name": "Carol", "status": "error", "user": {"id": 7}}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("test".to_string(), json_lines).unwrap();
+        let data = JsonlData::new(reader).unwrap();
+
+        let rows = |expr: &str| -> Vec<usize> {
+            let filter = Filter::parse(expr).unwrap();
+            filter.select(&data.reader).map(|(i, _)| i).collect()
+        };
+
+        assert_eq!(rows(r#"age > 30 && name =~ "^A""#), vec![1]);
+        assert_eq!(rows("exists(gender)"), vec![2]);
+        assert_eq!(rows("!exists(age)"), vec![3]);
+        assert_eq!(rows("user.id >= 7 || tags[0] == 'a'"), vec![0, 3]);
+        assert_eq!(rows("status == 'error' && !(age < 40)"), vec![1, 3]);
+        assert_eq!(rows("gender == null"), vec![2]);
+        assert_eq!(rows(r#"name !~ "^[AB]""#), vec![3]);
+
+        let filter = Filter::parse("status == \"error\"").unwrap();
+        assert_eq!(filter.count(&data.reader), 3);
+        let subset = data.filtered(&filter).unwrap();
+        assert_eq!(subset.len(), 3);
+        assert!(!subset.keys_seen.as_ref().unwrap().contains("tags[0]"));
+
+        assert!(Filter::parse("age >").is_err());
+        assert!(Filter::parse("age > 3 &&").is_err());
+        assert!(Filter::parse("name =~ \"(\"").is_err());
+        assert!(Filter::parse("(age > 3").is_err());
+
+        // Paths as the string profile reports them match any element
+        let orders = vec![
+            json!({"items": [{"id": "a1"}, {"id": "b2", "note": "gift"}], "tags": ["x"]}),
+            json!({"items": [{"id": "c3"}], "tags": []}),
+        ];
+        let profiles = profile::profile_records(&orders);
+        assert!(profiles.contains_key("items[].id") && profiles.contains_key("tags[]"));
+        let rows = |expr: &str| -> Vec<usize> {
+            let filter = Filter::parse(expr).unwrap();
+            (0..orders.len())
+                .filter(|&row| filter.matches(&orders[row]))
+                .collect()
+        };
+        assert_eq!(rows("exists(tags[])"), vec![0]);
+        assert_eq!(rows("items[].id == 'c3'"), vec![1]);
+        assert_eq!(rows("exists(items[].note)"), vec![0]);
+        assert_eq!(rows(r#"items[].id =~ "^[ab]""#), vec![0]);
+        assert_eq!(rows("!exists(items[].note)"), vec![1]);
+    }
+
+    #[test]
+    fn test_projection() {
+        let record = json!({
+            "id": 1,
+            "user": {"name": "Alice", "address": {"city": "Oslo", "zip": "0150"}},
+            "items": [{"id": 10, "qty": 1}, {"id": 11, "qty": 2}],
+            "debug": true
+        });
+
+        let nested = Projection::parse(
+            &["id", "$.user.address['city']", "items[*].id", "nope"],
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            nested.apply(&record),
+            json!({"id": 1, "user": {"address": {"city": "Oslo"}}, "items": [{"id": 10}, {"id": 11}]})
+        );
+
+        let flat = Projection::parse(&["$['user'].address", "items[1]"], true).unwrap();
+        assert_eq!(
+            flat.apply(&record),
+            json!({"user.address.city": "Oslo", "user.address.zip": "0150", "items[1].id": 11, "items[1].qty": 2})
+        );
+
+        // Recursive descent would change the meaning, so it is refused
+        assert!(Projection::parse(&["$..name"], false).is_err());
+        assert!(Projection::parse(&["$.user..name"], false).is_err());
+
+        let reader = MemoryJsonlReader::from_strings(
+            "t".to_string(),
+            vec![r#"{"a": 1, "b": 2}"#, r#"{"b": 3}"#],
+        )
+        .unwrap();
+        let only_b = Projection::parse(&["b"], false).unwrap();
+        let projected: Vec<Value> = only_b.project(reader.iter()).collect();
+        assert_eq!(projected, vec![json!({"b": 2}), json!({"b": 3})]);
+    }
+
+    #[test]
+    fn test_group_by_aggregation() {
+        let json_lines = vec![
+            r#"{"gender": "f", "age": 30, "ts": "2025-07-22T12:00:00Z"}"#,
+            r#"{"gender": "m", "age": 40, "ts": "2025-07-22T12:05:00Z"}"#,
+            r#"{"gender": "f", "age": 25, "ts": "2025-07-22T12:09:00Z"}"#,
+            r#"{"age": 50, "ts": "2025-07-22T12:01:00Z"}"#,
+            r#"{"gender": "f", "ts": "2025-07-22T12:02:00Z"}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("test".to_string(), json_lines).unwrap();
+
+        let group_by = GroupBy::parse(
+            &["gender"],
+            "count, count(age), avg(age), max(ts), min(age)",
+        )
+        .unwrap();
+        let result = group_by.run(reader.iter());
+
+        assert_eq!(
+            result.columns,
+            vec![
+                "gender",
+                "count",
+                "count(age)",
+                "avg(age)",
+                "max(ts)",
+                "min(age)"
+            ]
+        );
+        // The missing group key becomes an explicit null group, sorted first
+        assert_eq!(
+            result.to_records(),
+            vec![
+                json!({"gender": null, "count": 1, "count(age)": 1, "avg(age)": 50, "max(ts)": "2025-07-22T12:01:00Z", "min(age)": 50}),
+                json!({"gender": "f", "count": 3, "count(age)": 2, "avg(age)": 27.5, "max(ts)": "2025-07-22T12:09:00Z", "min(age)": 25}),
+                json!({"gender": "m", "count": 1, "count(age)": 1, "avg(age)": 40, "max(ts)": "2025-07-22T12:05:00Z", "min(age)": 40}),
+            ]
+        );
+
+        assert!(GroupBy::parse(&["gender"], "median(age)").is_err());
+        assert!(GroupBy::parse(&["gender"], "avg(age").is_err());
+    }
+
+    fn join_fixtures() -> (MemoryJsonlReader, MemoryJsonlReader) {
+        let events = MemoryJsonlReader::from_strings(
+            "events".to_string(),
+            vec![
+                r#"{"event": "login", "user_id": 1, "ts": 10}"#,
+                r#"{"event": "click", "user_id": 2, "ts": 11}"#,
+                r#"{"event": "login", "user_id": 3, "ts": 12}"#,
+                r#"{"event": "click", "user_id": 1, "ts": 13}"#,
+                r#"{"event": "boot", "ts": 14}"#,
+            ],
+        )
+        .unwrap();
+        let users = MemoryJsonlReader::from_strings(
+            "users".to_string(),
+            vec![
+                r#"{"id": 1, "name": "Alice", "ts": 1}"#,
+                r#"{"id": 2, "name": "Bob", "ts": 2}"#,
+                r#"{"id": 4, "name": "Dan", "ts": 4}"#,
+            ],
+        )
+        .unwrap();
+        (events, users)
+    }
+
+    fn run_test_join(
+        join: &Join,
+        left: &MemoryJsonlReader,
+        right: &MemoryJsonlReader,
+    ) -> (Vec<Value>, join::JoinSummary) {
+        let mut out = Vec::new();
+        let records = |reader: &MemoryJsonlReader| -> Vec<io::Result<Value>> {
+            reader.iter().cloned().map(Ok).collect()
+        };
+        let summary = join.run(records(left), records(right), &mut out).unwrap();
+        let records = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        (records, summary)
+    }
+
+    #[test]
+    fn test_hash_join() {
+        let (events, users) = join_fixtures();
+        let mut join = Join::new(
+            KeyPath::parse("user_id").unwrap(),
+            KeyPath::parse("id").unwrap(),
+        );
+
+        let (records, summary) = run_test_join(&join, &events, &users);
+        assert_eq!(records.len(), 3);
+        assert_eq!(
+            records[0],
+            json!({"event": "login", "user_id": 1, "id": 1, "name": "Alice", "left_ts": 10, "right_ts": 1})
+        );
+        assert_eq!(summary.matched_pairs, 3);
+        assert_eq!(summary.unmatched_left_keys, vec![json!(3)]);
+        assert_eq!(summary.unmatched_right_keys, vec![json!(4)]);
+        assert_eq!(summary.left_without_key, 1);
+        // The report printed to stderr when the records go to stdout
+        let report = summary.report("events", "users");
+        assert!(report.contains("Unmatched keys in events: 1 (1 records without a key)\n\t3\n"));
+        assert!(report.contains("Unmatched keys in users: 1 (0 records without a key)\n\t4\n"));
+
+        join.kind = JoinKind::Left;
+        join.conflicts = ConflictPolicy::LeftWins;
+        let (records, _) = run_test_join(&join, &events, &users);
+        assert_eq!(records.len(), 5);
+        assert_eq!(records[1]["ts"], 11);
+        assert_eq!(
+            records[2],
+            json!({"event": "login", "user_id": 3, "ts": 12})
+        );
+
+        join.kind = JoinKind::FullOuter;
+        join.conflicts = ConflictPolicy::RightWins;
+        let (records, _) = run_test_join(&join, &events, &users);
+        assert_eq!(records.len(), 6);
+        assert_eq!(records[1]["ts"], 2);
+        assert_eq!(records[5], json!({"id": 4, "name": "Dan", "ts": 4}));
+    }
+
+    #[test]
+    fn test_sort_merge_join_matches_hash_join() {
+        let (events, users) = join_fixtures();
+        let mut join = Join::new(
+            KeyPath::parse("user_id").unwrap(),
+            KeyPath::parse("id").unwrap(),
+        );
+        join.kind = JoinKind::FullOuter;
+
+        let (mut hashed, hash_summary) = run_test_join(&join, &events, &users);
+        // A run size of 2 forces the inputs through several temp-file runs
+        join.strategy = JoinStrategy::SortMerge { run_size: 2 };
+        let (mut merged, merge_summary) = run_test_join(&join, &events, &users);
+
+        assert_eq!(hash_summary, merge_summary);
+        hashed.sort_by(value::compare_values);
+        merged.sort_by(value::compare_values);
+        assert_eq!(hashed, merged);
+
+        // Both strategies match keys the same way: numbers by value
+        let left = MemoryJsonlReader::from_strings(
+            "l".to_string(),
+            vec![
+                r#"{"user_id": 1.0}"#,
+                r#"{"user_id": "1"}"#,
+                r#"{"user_id": [2.0]}"#,
+            ],
+        )
+        .unwrap();
+        let right = MemoryJsonlReader::from_strings(
+            "r".to_string(),
+            vec![r#"{"id": 1, "n": 1}"#, r#"{"id": [2], "n": 2}"#],
+        )
+        .unwrap();
+        for strategy in [JoinStrategy::Hash, JoinStrategy::SortMerge { run_size: 1 }] {
+            join.strategy = strategy;
+            let (_, summary) = run_test_join(&join, &left, &right);
+            assert_eq!(summary.matched_pairs, 2, "{:?}", strategy);
+            assert_eq!(summary.unmatched_left_keys, vec![json!("1")]);
+        }
+    }
+
+    #[test]
+    fn test_duplicates_and_dedup() {
+        let json_lines = vec![
+            r#"{"id": 1, "name": "Alice"}"#,
+            r#"{"name": "Alice", "id": 1}"#,
+            r#"{"id": 2, "name": "Bob"}"#,
+            r#"{"id": 2, "name": "Bob", "email": "bob@example.com"}"#,
+            r#"{"name": "NoId"}"#,
+            r#"{"name": "NoId"}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("test".to_string(), json_lines).unwrap();
+        let data = JsonlData::new(reader).unwrap();
+
+        let exact: Vec<Vec<usize>> = data
+            .find_duplicate_rows()
+            .into_iter()
+            .map(|g| g.rows)
+            .collect();
+        assert_eq!(exact, vec![vec![0, 1], vec![4, 5]]);
+
+        let id = KeyPath::parse("id").unwrap();
+        let by_id = data.find_duplicate_keys(&id);
+        assert_eq!(by_id.len(), 2);
+        assert_eq!(by_id[1].key, "2");
+        assert_eq!(by_id[1].rows, vec![2, 3]);
+
+        assert_eq!(data.dedup_rows(None, KeepPolicy::First), vec![0, 2, 3, 4]);
+        assert_eq!(
+            data.dedup_rows(Some(&id), KeepPolicy::First),
+            vec![0, 2, 4, 5]
+        );
+        assert_eq!(
+            data.dedup_rows(Some(&id), KeepPolicy::Last),
+            vec![1, 3, 4, 5]
+        );
+        assert_eq!(
+            data.dedup_rows(Some(&id), KeepPolicy::MostKeys),
+            vec![0, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let records = [
+            json!({"user": {"id": 2}, "ts": 5, "n": 0}),
+            json!({"user": {"id": 1}, "ts": 9, "n": 1}),
+            json!({"ts": 1, "n": 2}),
+            json!({"user": {"id": "abc"}, "ts": 3, "n": 3}),
+            json!({"user": {"id": 2}, "ts": 7, "n": 4}),
+            json!({"user": {"id": null}, "ts": 2, "n": 5}),
+            json!({"user": {"id": 1}, "ts": 9, "n": 6}),
+        ];
+        let spec = SortSpec::parse("user.id desc, ts asc").unwrap();
+
+        let mut out = Vec::new();
+        // A run size of 2 forces several runs through temp files
+        let summary = spec
+            .sort(records.iter().cloned().map(Ok), 2, &mut out)
+            .unwrap();
+        assert_eq!(summary.rows, 7);
+        assert_eq!(summary.runs, 4);
+
+        let order: Vec<i64> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|l| {
+                serde_json::from_str::<Value>(l).unwrap()["n"]
+                    .as_i64()
+                    .unwrap()
+            })
+            .collect();
+        // Strings sort above numbers, null below them, and the missing key last;
+        // equal keys keep their input order
+        assert_eq!(order, vec![3, 0, 4, 1, 6, 5, 2]);
+
+        // With more runs than the fan-in, runs are merged in several passes
+        // and equal keys still keep their input order
+        let by_key = |a: &Value, b: &Value| value::compare_values(&a["k"], &b["k"]);
+        let mut sorter = external::ExternalSorter::new(1, by_key)
+            .unwrap()
+            .with_fan_in(2);
+        for n in 0..9 {
+            sorter.push(json!({"k": n % 3, "n": n})).unwrap();
+        }
+        let sorted = sorter.finish().unwrap();
+        assert_eq!(sorted.runs(), 9);
+        let order: Vec<i64> = sorted.map(|r| r.unwrap()["n"].as_i64().unwrap()).collect();
+        assert_eq!(order, vec![0, 3, 6, 1, 4, 7, 2, 5, 8]);
+
+        assert!(SortSpec::parse("ts sideways").is_err());
+        assert!(SortSpec::parse(" , ").is_err());
+    }
+
+    fn read_split_output(path: &std::path::Path) -> Vec<Value> {
+        jsonl::stream_jsonl(path)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_by_rows_and_bytes() {
+        let dir = external::TempDir::new("split-test").unwrap();
+        let records: Vec<Value> = (0..5).map(|i| json!({"n": i})).collect();
+
+        let mut splitter = Splitter::new(SplitMode::Rows(2));
+        splitter.template = dir
+            .path()
+            .join("{name}-{index}.jsonl")
+            .to_string_lossy()
+            .to_string();
+        let summary = splitter
+            .split("events.jsonl", records.iter().cloned().map(Ok))
+            .unwrap();
+        let counts: Vec<usize> = summary.files.values().copied().collect();
+        assert_eq!(counts, vec![2, 2, 1]);
+        assert_eq!(
+            read_split_output(&dir.path().join("events-2.jsonl")),
+            vec![json!({"n": 4})]
+        );
+
+        // Each line of `{"n":0}` is 8 bytes, so 20 bytes hold two records
+        splitter.mode = SplitMode::Bytes(20);
+        splitter.template = dir
+            .path()
+            .join("bytes-{index}.jsonl")
+            .to_string_lossy()
+            .to_string();
+        let summary = splitter
+            .split("events.jsonl", records.into_iter().map(Ok))
+            .unwrap();
+        let counts: Vec<usize> = summary.files.values().copied().collect();
+        assert_eq!(counts, vec![2, 2, 1]);
+    }
+
+    #[test]
+    fn test_partition_by_key_with_open_file_cap() {
+        let dir = external::TempDir::new("partition-test").unwrap();
+        let countries = ["no", "se", "no", "dk", "se", "no"];
+        let mut records: Vec<Value> = countries
+            .iter()
+            .enumerate()
+            .map(|(i, c)| json!({"n": i, "country": c}))
+            .collect();
+        records.push(json!({"n": 6}));
+
+        let mut splitter = Splitter::new(SplitMode::Key(KeyPath::parse("country").unwrap()));
+        splitter.template = dir.path().join("{key}.jsonl").to_string_lossy().to_string();
+        // Only one file open at a time, so files are closed and appended to again
+        splitter.max_open = 1;
+        let summary = splitter
+            .split("data.jsonl", records.into_iter().map(Ok))
+            .unwrap();
+
+        assert_eq!(summary.files.len(), 4);
+        let norway = read_split_output(&dir.path().join("no.jsonl"));
+        let numbers: Vec<i64> = norway.iter().map(|r| r["n"].as_i64().unwrap()).collect();
+        assert_eq!(numbers, vec![0, 2, 5]);
+        assert_eq!(read_split_output(&dir.path().join("null.jsonl")).len(), 1);
+
+        // Values that sanitize to the same file name are not merged
+        let records = [json!({"country": "a/b"}), json!({"country": "a_b"})];
+        let err = splitter
+            .split("data.jsonl", records.into_iter().map(Ok))
+            .unwrap_err();
+        assert!(err.to_string().contains("both map to"));
+
+        // A template without the placeholder would write every output to one file
+        splitter.template = dir.path().join("all.jsonl").to_string_lossy().to_string();
+        assert!(splitter.split("data.jsonl", std::iter::empty()).is_err());
+        let mut chunks = Splitter::new(SplitMode::Rows(1));
+        chunks.template = "{name}.jsonl".to_string();
+        assert!(chunks.split("data.jsonl", std::iter::empty()).is_err());
+    }
+
+    #[test]
+    fn test_sampling_is_seeded_and_stratified() {
+        let records: Vec<Value> = (0..1000)
+            .map(|i| json!({"n": i, "group": if i % 10 == 0 { "rare" } else { "common" }}))
+            .collect();
+        let stream = || records.iter().cloned().map(Ok);
+
+        let sampler = Sampler::new(SampleSize::Count(50), 7);
+        let first = sampler.sample(stream()).unwrap();
+        let second = sampler.sample(stream()).unwrap();
+        assert_eq!(first.rows.len(), 50);
+        assert_eq!(first.total_rows, 1000);
+        assert_eq!(first.rows, second.rows);
+        assert!(first.rows.windows(2).all(|w| w[0].0 < w[1].0));
+        let other_seed = Sampler::new(SampleSize::Count(50), 8)
+            .sample(stream())
+            .unwrap();
+        assert_ne!(first.rows, other_seed.rows);
+
+        let fraction = Sampler::new(SampleSize::Fraction(0.1), 7)
+            .sample(stream())
+            .unwrap();
+        assert!((50..150).contains(&fraction.rows.len()));
+
+        let mut stratified = Sampler::new(SampleSize::Count(5), 7);
+        stratified.stratify = Some(KeyPath::parse("group").unwrap());
+        let sample = stratified.sample(stream()).unwrap();
+        assert_eq!(sample.strata["\"rare\""], (100, 5));
+        assert_eq!(sample.strata["\"common\""], (900, 5));
+
+        // The string "null", a JSON null and a missing key are three strata
+        let mixed = [
+            json!({"group": "null"}),
+            json!({"group": null}),
+            json!({"n": 1}),
+            json!({"group": 1}),
+            json!({"group": "1"}),
+        ];
+        let strata = stratified
+            .sample(mixed.iter().cloned().map(Ok))
+            .unwrap()
+            .strata;
+        let names: Vec<&str> = strata.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            vec!["\"1\"", "\"null\"", sample::MISSING_STRATUM, "1", "null"]
+        );
+
+        let data = JsonlData::new(MemoryJsonlReader::new(
+            "sample".to_string(),
+            sample.rows.iter().map(|(_, r)| r.clone()).collect(),
+        ))
+        .unwrap()
+        .with_sample_info(sample.info(&stratified));
+        let info = data.sample.as_ref().unwrap();
+        assert_eq!((info.sampled_rows, info.total_rows), (10, 1000));
+        assert!(info.to_string().contains("per group stratum, seed 7"));
+    }
+
+    #[test]
+    fn test_row_selection_with_line_index() {
+        let dir = external::TempDir::new("index-test").unwrap();
+        let path = dir.path().join("rows.jsonl");
+        let lines: Vec<String> = (0..500).map(|i| format!("{{\"n\":{}}}", i)).collect();
+        // Blank lines are not rows
+        std::fs::write(
+            &path,
+            format!(
+                "{}\n\n{}\n",
+                lines[..250].join("\n"),
+                lines[250..].join("\n")
+            ),
+        )
+        .unwrap();
+
+        let select = |selection: RowSelection, index: Option<&LineIndex>| {
+            let mut out = Vec::new();
+            index::write_rows(&path, &selection, index, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let expected = |range: std::ops::Range<usize>| lines[range].join("\n") + "\n";
+
+        assert_eq!(select(RowSelection::Head(3), None), expected(0..3));
+        assert_eq!(select(RowSelection::Tail(300), None), expected(200..500));
+        assert_eq!(select(RowSelection::Tail(0), None), "");
+        assert_eq!(select(RowSelection::Tail(1000), None), expected(0..500));
+        let range = RowSelection::parse_range("240..260").unwrap();
+        assert_eq!(select(range, None), expected(240..260));
+
+        assert!(LineIndex::load(&path).unwrap().is_none());
+        let index = LineIndex::load_or_build(&path).unwrap();
+        assert_eq!(index.len(), 500);
+        let loaded = LineIndex::load(&path).unwrap().unwrap();
+        assert_eq!(loaded, index);
+        assert_eq!(loaded.offset(500), None);
+        assert_eq!(select(range, Some(&loaded)), expected(240..260));
+        assert_eq!(
+            select(RowSelection::Tail(2), Some(&index)),
+            expected(498..500)
+        );
+        assert_eq!(
+            select(RowSelection::parse_range("499..").unwrap(), Some(&index)),
+            expected(499..500)
+        );
+
+        // Lines longer than a block are put together from their pieces
+        let long: Vec<String> = (0..3)
+            .map(|i| format!("{{\"s\":\"{}\"}}", i.to_string().repeat(100_000)))
+            .collect();
+        let long_path = dir.path().join("long.jsonl");
+        std::fs::write(&long_path, long.join("\r\n")).unwrap();
+        let mut out = Vec::new();
+        index::write_rows(&long_path, &RowSelection::Tail(2), None, &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), long[1..].join("\n") + "\n");
+
+        // Changing the file invalidates the sidecar
+        std::fs::write(&path, lines[..10].join("\n")).unwrap();
+        assert!(LineIndex::load(&path).unwrap().is_none());
+        assert_eq!(select(RowSelection::Tail(1), None), expected(9..10));
+    }
+
+    #[test]
+    fn test_mmap_reader_matches_file_reader() {
+        let mapped =
+            JsonlData::new(MmapJsonlReader::new(PathBuf::from("data/test.jsonl"))).unwrap();
+        let loaded =
+            JsonlData::new(FileJsonlReader::new(PathBuf::from("data/test.jsonl"))).unwrap();
+        assert_eq!(mapped.len(), loaded.len());
+        assert_eq!(mapped.key_freqs, loaded.key_freqs);
+        assert_eq!(mapped.rows_with_missing_keys, loaded.rows_with_missing_keys);
+        assert!(mapped.reader.iter().eq(loaded.reader.iter()));
+
+        let mut reader = mapped.reader;
+        reader.replace(1, json!({"id": 2, "edited": true})).unwrap();
+        reader.get_mut(2).unwrap()["name"] = json!("Charles");
+        reader.push(json!({"id": 11})).unwrap();
+        reader.clear_cache();
+        assert_eq!(reader.get(1), Some(&json!({"id": 2, "edited": true})));
+        assert_eq!(reader.get(2).unwrap()["name"], "Charles");
+        assert_eq!(reader.get(10), Some(&json!({"id": 11})));
+        assert_eq!(reader.len(), 11);
+        // The file itself is untouched
+        assert_eq!(reader.read_record(1).unwrap().unwrap()["name"], "Bob");
+
+        let dir = external::TempDir::new("mmap-test").unwrap();
+        let bad = dir.path().join("bad.jsonl");
+        std::fs::write(&bad, "{\"a\": 1}\n{oops\n").unwrap();
+        let error = MmapJsonlReader::new(bad).load().unwrap_err();
+        assert!(error.to_string().starts_with("record 1:"));
+    }
+
+    #[test]
+    fn test_mmap_analysis_leaves_cache_empty() {
+        let mapped =
+            JsonlData::new(MmapJsonlReader::new(PathBuf::from("data/test.jsonl"))).unwrap();
+        let loaded =
+            JsonlData::new(FileJsonlReader::new(PathBuf::from("data/test.jsonl"))).unwrap();
+        assert_eq!(mapped.string_profiles(), loaded.string_profiles());
+        assert_eq!(mapped.find_duplicate_rows(), loaded.find_duplicate_rows());
+        assert_eq!(mapped.reader.cached_records(), 0);
+
+        // Command passes parse temporaries too
+        let by_name = GroupBy::parse(&["name"], "count").unwrap();
+        let groups = jsonl::with_records(&mapped.reader, |records| by_name.run(records)).unwrap();
+        assert_eq!(groups, by_name.run(loaded.reader.iter()));
+        let mut written = Vec::new();
+        mapped.write_jsonl(&mut written).unwrap();
+        let filter = Filter::parse("exists(name)").unwrap();
+        assert_eq!(
+            mapped.filtered(&filter).unwrap().len(),
+            loaded.filtered(&filter).unwrap().len()
+        );
+        assert_eq!(mapped.reader.cached_records(), 0);
+
+        mapped.reader.get(0).unwrap();
+        assert_eq!(mapped.reader.cached_records(), 1);
+
+        // A record that no longer parses is an error, not a skipped row
+        let dir = external::TempDir::new("mmap-changed").unwrap();
+        let path = dir.path().join("changed.jsonl");
+        std::fs::write(&path, "{\"a\": 1}\n{\"a\": 2}\n{\"a\": 3}\n").unwrap();
+        let mut reader = MmapJsonlReader::new(path.clone());
+        reader.load().unwrap();
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        io::Seek::seek(&mut file, io::SeekFrom::Start(14)).unwrap();
+        file.write_all(b"x").unwrap();
+        drop(file);
+        let rows: Vec<bool> = reader.records().map(|record| record.is_ok()).collect();
+        assert_eq!(rows, vec![true, false, true]);
+        let error = jsonl::with_records(&reader, |records| records.count()).unwrap_err();
+        assert!(error.to_string().starts_with("record 1:"));
+        assert!(reader.for_each_batch(&mut |_, _| {}).is_err());
+    }
+
+    #[test]
+    fn test_parallel_load_matches_sequential() {
+        let dir = external::TempDir::new("parallel-test").unwrap();
+        let path = dir.path().join("rows.jsonl");
+        let lines: Vec<String> = (0..20_000)
+            .map(|i| match i % 3 {
+                0 => json!({"id": i, "tags": ["a", "b"]}).to_string(),
+                1 => json!({"id": i, "user": {"name": "x"}}).to_string(),
+                _ => json!({"id": i}).to_string(),
+            })
+            .collect();
+        std::fs::write(&path, lines.join("\n\n")).unwrap();
+
+        let sequential = JsonlData::new(FileJsonlReader::new(path.clone())).unwrap();
+        let reader = FileJsonlReader::new(path.clone()).with_threads(4);
+        let parallel = JsonlData::with_threads(reader, 4).unwrap();
+        assert!(sequential.reader.iter().eq(parallel.reader.iter()));
+        assert_eq!(sequential.keys_seen, parallel.keys_seen);
+        assert_eq!(sequential.key_freqs, parallel.key_freqs);
+        assert_eq!(
+            sequential.rows_with_missing_keys,
+            parallel.rows_with_missing_keys
+        );
+
+        // The first bad line is reported, as in the sequential path
+        let bad = dir.path().join("bad.jsonl");
+        let mut broken = lines.clone();
+        broken[15_000] = "{oops".to_string();
+        broken[19_000] = "[".to_string();
+        std::fs::write(&bad, broken.join("\n")).unwrap();
+        let sequential_error = FileJsonlReader::new(bad.clone()).load().unwrap_err();
+        let parallel_error = FileJsonlReader::new(bad)
+            .with_threads(4)
+            .load()
+            .unwrap_err();
+        assert_eq!(sequential_error.to_string(), parallel_error.to_string());
+        assert!(sequential_error.to_string().starts_with("line 15001: "));
+    }
+
+    #[test]
+    fn test_progress_callback() {
+        let dir = external::TempDir::new("progress-test").unwrap();
+        let path = dir.path().join("rows.jsonl");
+        let lines: Vec<String> = (0..12_345).map(|i| json!({"n": i}).to_string()).collect();
+        std::fs::write(&path, lines.join("\n") + "\n").unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let callback: progress::ProgressCallback =
+            std::sync::Arc::new(move |p: &progress::Progress| seen.lock().unwrap().push(p.clone()));
+        JsonlData::with_progress(FileJsonlReader::new(path), 1, Some(callback)).unwrap();
+        // Without a callback nothing is reported
+        JsonlData::new(FileJsonlReader::new(PathBuf::from("data/test.jsonl"))).unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(
+            events
+                .iter()
+                .all(|p| p.total_bytes.is_none_or(|total| total == size))
+        );
+        let read = events
+            .iter()
+            .find(|p| p.done && p.stage == "read" && p.total_bytes == Some(size))
+            .unwrap();
+        assert_eq!((read.rows, read.bytes), (12_345, size));
+        assert!(
+            events
+                .iter()
+                .any(|p| p.done && p.stage == "keys" && p.total_rows == Some(12_345))
+        );
+
+        let halfway = progress::Progress {
+            stage: "read",
+            rows: 500,
+            bytes: 1024,
+            total_rows: None,
+            total_bytes: Some(2048),
+            elapsed: std::time::Duration::from_secs(10),
+            done: false,
+        };
+        assert_eq!(halfway.eta(), Some(std::time::Duration::from_secs(10)));
+        assert_eq!(
+            halfway.to_string(),
+            "read: 500 rows (1.0 KB), 50 rows/s, 102 B/s, ETA 0:10"
+        );
+    }
+
+    #[test]
+    fn test_follow_appends_rotation_and_truncation() {
+        use std::io::Write as _;
+
+        let dir = external::TempDir::new("follow-test").unwrap();
+        let path = dir.path().join("app.jsonl");
+        let append = |text: &str| {
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(text.as_bytes()).unwrap();
+        };
+
+        append("{\"a\": 1}\n{\"a\": 2, \"b\": 1}\n");
+        let mut follower = Follower::new(path.clone());
+        follower.start().unwrap();
+        assert_eq!(follower.stats().rows, 2);
+        assert!(follower.poll().unwrap().is_empty());
+
+        // A line is only parsed once its newline has been written
+        append("{\"a\": 3, \"c\":");
+        assert!(follower.poll().unwrap().is_empty());
+        append(" {\"d\": true}}\n");
+        assert_eq!(
+            follower.poll().unwrap(),
+            vec![
+                FollowEvent::NewKey {
+                    path: "c".to_string(),
+                    row: 2
+                },
+                FollowEvent::NewKey {
+                    path: "c.d".to_string(),
+                    row: 2
+                },
+            ]
+        );
+        assert_eq!(follower.stats().key_counts["a"], 3);
+
+        // Rotation: the path is recreated as a new file
+        std::fs::rename(&path, dir.path().join("app.jsonl.1")).unwrap();
+        append("{\"e\": 1}\n");
+        assert_eq!(
+            follower.poll().unwrap(),
+            vec![
+                FollowEvent::Rotated,
+                FollowEvent::NewKey {
+                    path: "e".to_string(),
+                    row: 3
+                },
+            ]
+        );
+
+        // Truncation: the file is emptied in place and written again
+        std::fs::OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        append("{}\n");
+        assert_eq!(follower.poll().unwrap(), vec![FollowEvent::Truncated]);
+        assert_eq!(follower.stats().rows, 5);
+    }
+
+    #[test]
+    fn test_follow_reads_large_appends_in_chunks() {
+        let dir = external::TempDir::new("follow-chunks").unwrap();
+        let path = dir.path().join("big.jsonl");
+        std::fs::write(&path, "").unwrap();
+        let mut follower = Follower::new(path.clone());
+        follower.start().unwrap();
+
+        // Lines longer than a read chunk and many lines ending mid-chunk
+        let long = json!({"blob": "x".repeat(200_000)}).to_string();
+        let short: Vec<String> = (0..10_000).map(|i| json!({"n": i}).to_string()).collect();
+        std::fs::write(&path, format!("{long}\n{}\n{long}\n", short.join("\n"))).unwrap();
+
+        let events = follower.poll().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(follower.stats().rows, 10_002);
+        assert_eq!(follower.stats().key_counts["blob"], 2);
+        assert_eq!(follower.stats().key_counts["n"], 10_000);
+    }
+
+    #[test]
+    fn test_convert_to_csv_and_tsv() {
+        let records = vec![
+            json!({"id": 1, "user": {"name": "Ann, \"A\""}, "tags": ["x", "y"]}),
+            json!({"id": 2, "user": {"name": "Bob\tB"}, "note": null}),
+            json!({"id": 3, "note": "line\nbreak"}),
+        ];
+        let reader = MemoryJsonlReader::new("convert".to_string(), records);
+
+        let export = TableExport::discover(TableFormat::Csv, reader.iter());
+        assert_eq!(export.columns, vec!["id", "note", "user.name", "tags"]);
+        let mut csv = Vec::new();
+        assert_eq!(export.write(reader.iter(), &mut csv).unwrap(), 3);
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,note,user.name,tags\r\n\
+             1,,\"Ann, \"\"A\"\"\",\"[\"\"x\"\",\"\"y\"\"]\"\r\n\
+             2,null,Bob\tB,\r\n\
+             3,\"line\nbreak\",,\r\n"
+        );
+
+        let export = TableExport {
+            format: TableFormat::Tsv,
+            columns: vec!["user.name".to_string(), "note".to_string()],
+        };
+        let mut tsv = Vec::new();
+        export.write(reader.iter(), &mut tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(tsv).unwrap(),
+            "user.name\tnote\nAnn, \"A\"\t\nBob\\tB\tnull\n\tline\\nbreak\n"
+        );
+
+        // A column naming a parent object holds the whole object as JSON
+        let export = TableExport {
+            format: TableFormat::Csv,
+            columns: vec!["id".to_string(), "user".to_string()],
+        };
+        let mut csv = Vec::new();
+        export.write(reader.iter(), &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "id,user\r\n\
+             1,\"{\"\"name\"\":\"\"Ann, \\\"\"A\\\"\"\"\"}\"\r\n\
+             2,\"{\"\"name\"\":\"\"Bob\\tB\"\"}\"\r\n\
+             3,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_import_with_type_inference() {
+        use import::ColumnType;
+
+        let dir = external::TempDir::new("import-test").unwrap();
+        let path = dir.path().join("partners.csv");
+        std::fs::write(
+            &path,
+            "id,zip,score,active,joined,address.city,note\r\n\
+             1,0042,1.5,true,2025-07-22T12:00:00Z,Oslo,\"multi\nline, \"\"quoted\"\"\"\r\n\
+             2,1234,2,FALSE,2025-07-23,Bergen,\r\n\
+             3,,null,true,,,plain\r\n",
+        )
+        .unwrap();
+
+        let mut options = CsvOptions::new(TableFormat::Csv);
+        options.nested = true;
+        options.literal_null = true;
+        let data = JsonlData::new(CsvJsonlReader::new(path.clone(), options)).unwrap();
+        let types: Vec<ColumnType> = data.reader.columns().iter().map(|(_, t)| *t).collect();
+        assert_eq!(
+            types,
+            vec![
+                ColumnType::Int,
+                ColumnType::String,
+                ColumnType::Float,
+                ColumnType::Bool,
+                ColumnType::Timestamp,
+                ColumnType::String,
+                ColumnType::String,
+            ]
+        );
+        assert_eq!(
+            data.get(0),
+            Some(&json!({
+                "id": 1, "zip": "0042", "score": 1.5, "active": true,
+                "joined": "2025-07-22T12:00:00Z", "address": {"city": "Oslo"},
+                "note": "multi\nline, \"quoted\""
+            }))
+        );
+        // Empty cells leave the key out, `null` is a JSON null
+        assert_eq!(
+            data.get(2),
+            Some(&json!({"id": 3, "score": null, "active": true, "note": "plain"}))
+        );
+        assert_eq!(data.rows_with_missing_keys, Some(vec![1, 2]));
+
+        // Without --literal-null, `null` is text; integers beyond 64 bits stay strings
+        let wide = dir.path().join("wide.csv");
+        std::fs::write(
+            &wide,
+            "score,big,huge\n\
+             1.5,18446744073709551615,123456789012345678901234567890\n\
+             null,-9223372036854775808,1\n",
+        )
+        .unwrap();
+        let mut reader = CsvJsonlReader::new(wide, CsvOptions::new(TableFormat::Csv));
+        reader.load().unwrap();
+        let types: Vec<ColumnType> = reader.columns().iter().map(|(_, t)| *t).collect();
+        assert_eq!(
+            types,
+            vec![ColumnType::String, ColumnType::Int, ColumnType::String]
+        );
+        assert_eq!(
+            reader.get(0),
+            Some(&json!({
+                "score": "1.5", "big": 18446744073709551615u64,
+                "huge": "123456789012345678901234567890"
+            }))
+        );
+        assert_eq!(
+            reader.get(1),
+            Some(&json!({"score": "null", "big": i64::MIN, "huge": "1"}))
+        );
+
+        // Round trip through the TSV export, arrays and all
+        let records = vec![
+            json!({"id": 1, "user": {"name": "tab\there"}, "tags": ["a", "b"]}),
+            json!({"id": 2, "user": {"name": "x"}, "tags": []}),
+        ];
+        let source = MemoryJsonlReader::new("records".to_string(), records.clone());
+        let tsv = dir.path().join("records.tsv");
+        TableExport::discover(TableFormat::Tsv, source.iter())
+            .write(source.iter(), std::fs::File::create(&tsv).unwrap())
+            .unwrap();
+        let mut options = CsvOptions::new(TableFormat::Tsv);
+        options.nested = true;
+        let mut reader = CsvJsonlReader::new(tsv, options);
+        reader.load().unwrap();
+        assert!(reader.iter().eq(records.iter()));
+    }
+
+    #[test]
+    fn test_parquet_export_schema_and_coercion() {
+        use ::parquet::file::reader::{FileReader, SerializedFileReader};
+        use ::parquet::schema::printer::print_schema;
+
+        let records = vec![
+            json!({"id": 1, "score": 1.5, "user": {"name": "Ann"}, "tags": ["x", "y"]}),
+            json!({"id": 2, "score": 2, "user": {"name": "Bob", "age": 40}, "tags": []}),
+            json!({"id": "three", "score": null, "tags": ["z"]}),
+        ];
+        let reader = MemoryJsonlReader::new("parquet".to_string(), records);
+        let export = ParquetExport::infer(reader.iter());
+
+        let mut schema = Vec::new();
+        print_schema(&mut schema, &export.schema().unwrap());
+        let schema = String::from_utf8(schema).unwrap();
+        assert!(schema.contains("OPTIONAL INT64 id;"), "{}", schema);
+        assert!(schema.contains("OPTIONAL DOUBLE score;"), "{}", schema);
+        assert!(schema.contains("REQUIRED group tags (LIST)"), "{}", schema);
+        assert!(
+            schema.contains("REQUIRED BYTE_ARRAY element (STRING);"),
+            "{}",
+            schema
+        );
+        assert!(schema.contains("OPTIONAL group user {"), "{}", schema);
+        assert!(
+            schema.contains("REQUIRED BYTE_ARRAY name (STRING);"),
+            "{}",
+            schema
+        );
+        assert!(schema.contains("OPTIONAL INT64 age;"), "{}", schema);
+
+        let dir = external::TempDir::new("parquet-test").unwrap();
+        let path = dir.path().join("out.parquet");
+        let summary = export
+            .write(reader.iter(), std::fs::File::create(&path).unwrap())
+            .unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.row_groups, 1);
+        assert_eq!(
+            summary.errors,
+            vec![parquet_export::CoercionError {
+                row: 2,
+                path: "id".to_string(),
+                found: "string",
+            }]
+        );
+
+        let file = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        assert_eq!(file.metadata().file_metadata().num_rows(), 3);
+        let rows: Vec<String> = file
+            .into_iter()
+            .map(|row| row.unwrap().to_string())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                r#"{id: 1, score: 1.5, tags: ["x", "y"], user: {age: null, name: "Ann"}}"#,
+                r#"{id: 2, score: 2.0, tags: [], user: {age: 40, name: "Bob"}}"#,
+                r#"{id: null, score: null, tags: ["z"], user: null}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_array_and_concatenated_documents() {
+        use documents::{JsonLayout, read_json_documents};
+
+        let records = vec![
+            json!({"id": 1, "tags": ["a", "b"]}),
+            json!({"id": 2, "user": {"name": "Ann"}}),
+            json!("not an object"),
+        ];
+        let dir = external::TempDir::new("documents-test").unwrap();
+        let array = dir.path().join("array.json");
+        std::fs::write(
+            &array,
+            format!("\n  {}\n", serde_json::to_string_pretty(&records).unwrap()),
+        )
+        .unwrap();
+        let concatenated = dir.path().join("concatenated.json");
+        let pretty: Vec<String> = records
+            .iter()
+            .map(|record| serde_json::to_string_pretty(record).unwrap())
+            .collect();
+        std::fs::write(&concatenated, pretty.join("\n")).unwrap();
+
+        for (path, layout) in [
+            (&array, JsonLayout::Array),
+            (&concatenated, JsonLayout::Concatenated),
+        ] {
+            let mut reader = JsonDocumentReader::new(path.clone());
+            reader.load().unwrap();
+            assert_eq!(reader.layout(), Some(layout));
+            assert!(reader.iter().eq(records.iter()));
+
+            let mut jsonl = Vec::new();
+            let (_, rows) = read_json_documents(std::fs::File::open(path).unwrap(), |record| {
+                serde_json::to_writer(&mut jsonl, &record)?;
+                jsonl.push(b'\n');
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(rows, 3);
+            assert_eq!(
+                String::from_utf8(jsonl).unwrap(),
+                "{\"id\":1,\"tags\":[\"a\",\"b\"]}\n{\"id\":2,\"user\":{\"name\":\"Ann\"}}\n\"not an object\"\n"
+            );
+        }
+
+        let err = read_json_documents(&b"[{\"id\": 1}] {\"id\": 2}"[..], |_| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = read_json_documents(&b"{\"id\": 1} {\"id\": "[..], |_| Ok(())).unwrap_err();
+        assert!(err.to_string().starts_with("invalid record 1:"), "{}", err);
+    }
+
+    #[test]
+    fn test_canonical_key_order_escaping_and_check() {
+        let records = vec![
+            json!({"b": 1, "a": {"z": true, "y": [{"k": 2, "j": 1}]}, "c": "é"}),
+            json!({"c": "x", "b": 1.5e2}),
+        ];
+
+        let sorted = Canonicalizer::new(&KeyOrder::Sorted, &records);
+        assert_eq!(
+            sorted.format(&records[0]),
+            r#"{"a":{"y":[{"j":1,"k":2}],"z":true},"b":1,"c":"é"}"#
+        );
+        assert_eq!(sorted.format(&records[1]), r#"{"b":150.0,"c":"x"}"#);
+
+        let mut custom = Canonicalizer::new(&KeyOrder::parse("c,a,a.z,a.y[].k"), &records);
+        custom.ascii = true;
+        assert_eq!(
+            custom.format(&records[0]),
+            r#"{"c":"\u00e9","a":{"z":true,"y":[{"k":2,"j":1}]},"b":1}"#
+        );
+
+        // b and c are in both rows; a only in the first
+        let frequency = Canonicalizer::new(&KeyOrder::Frequency, &records);
+        assert_eq!(
+            frequency.format(&records[0]),
+            r#"{"b":1,"c":"é","a":{"y":[{"j":1,"k":2}],"z":true}}"#
+        );
+
+        let input = "{\"b\":150.0,\"c\":\"x\"}\n\n{\"c\": \"x\", \"b\": 1}\n{\"b\":2}\r\n";
+        let summary = sorted
+            .format_lines(input.as_bytes(), None::<io::Sink>)
+            .unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.changed_lines, vec![2, 3, 4]);
+        let mut output = Vec::new();
+        sorted
+            .format_lines(input.as_bytes(), Some(&mut output))
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "{\"b\":150.0,\"c\":\"x\"}\n{\"b\":1,\"c\":\"x\"}\n{\"b\":2}\n"
+        );
+        assert!(
+            sorted
+                .format_lines(output.as_bytes(), None::<io::Sink>)
+                .unwrap()
+                .is_canonical()
+        );
+        assert!(summary.lossy_lines.is_empty());
+
+        // Exponents and trailing zeros keep their value; extra digits do not
+        let input = concat!(
+            "{\"n\": 1e2, \"m\": 0.10, \"s\": \"-1e999\"}\n",
+            "{\"n\": 123456789012345678901234567890}\n",
+            "{\"n\": 0.10000000000000000001}\n",
+            "{\"n\": -18446744073709551615}\n",
+        );
+        let summary = sorted
+            .format_lines(input.as_bytes(), None::<io::Sink>)
+            .unwrap();
+        assert_eq!(summary.changed_lines, vec![1, 2, 3, 4]);
+        assert_eq!(summary.lossy_lines, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn test_missing_keys_by_row() {
+        let lines = vec![
+            r#"{"id": 1, "user": {"name": "Ann", "age": 30}}"#,
+            r#"{"id": 2, "user": {"name": "Bob"}}"#,
+            r#"{"id": 3, "user": {"name": "Cy", "age": 41}}"#,
+            r#"{"user": 4}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("missing".to_string(), lines).unwrap();
+        let data = JsonlData::with_threads(reader, 2).unwrap();
+
+        let missing = data.missing_keys_by_row();
+        assert_eq!(
+            missing,
+            vec![
+                jsonl::MissingKeys {
+                    row: 1,
+                    missing: vec!["user.age".to_string()],
+                    null: Vec::new(),
+                },
+                // user is not an object, so its keys do not apply
+                jsonl::MissingKeys {
+                    row: 3,
+                    missing: vec!["id".to_string()],
+                    null: Vec::new(),
+                },
+            ]
+        );
+        assert_eq!(data.rows_with_missing_keys, Some(vec![1, 3]));
+
+        let mut out = Vec::new();
+        assert_eq!(data.write_missing_keys(&mut out).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"row\":1,\"missing\":[\"user.age\"],\"null\":[]}\n\
+             {\"row\":3,\"missing\":[\"id\"],\"null\":[]}\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_keys_respect_optional_subtrees() {
+        use completeness::{Completeness, KeyStatus};
+
+        let lines = vec![
+            r#"{"id": 1, "address": {"city": "Oslo", "geo": {"lat": 59.9}}, "tags": ["a", "b"]}"#,
+            r#"{"id": 2, "address": {"city": "Bergen"}, "tags": ["c"]}"#,
+            r#"{"id": 3, "address": null, "tags": []}"#,
+            r#"{"id": null, "address": {"geo": {}}, "tags": [{"x": 1}]}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("completeness".to_string(), lines).unwrap();
+        let mut data = JsonlData::new(reader).unwrap();
+
+        let all_keys: Vec<String> = [
+            "address.geo.lat",
+            "address.geo",
+            "id",
+            "tags[1]",
+            "tags[0].x",
+        ]
+        .iter()
+        .map(|k| k.to_string())
+        .collect();
+        let statuses = |row: usize| -> Vec<KeyStatus> {
+            Completeness::statuses(data.get(row).unwrap(), &all_keys)
+                .into_iter()
+                .map(|(_, status)| status)
+                .collect()
+        };
+        use KeyStatus::*;
+        assert_eq!(
+            statuses(0),
+            vec![Present, Present, Present, Present, NotApplicable]
+        );
+        assert_eq!(
+            statuses(1),
+            vec![NotApplicable, Absent, Present, NotApplicable, NotApplicable]
+        );
+        assert_eq!(
+            statuses(2),
+            vec![
+                NotApplicable,
+                NotApplicable,
+                Present,
+                NotApplicable,
+                NotApplicable
+            ]
+        );
+        assert_eq!(
+            statuses(3),
+            vec![Absent, Present, Null, NotApplicable, Present]
+        );
+
+        // Row 2 (address null) is complete: nothing under it applies
+        assert_eq!(data.rows_with_missing_keys, Some(vec![1, 3]));
+
+        data.set_completeness(Completeness::new(
+            [
+                "address.geo".to_string(),
+                "address.geo.lat".to_string(),
+                "address.city".to_string(),
+            ],
+            false,
+        ));
+        assert_eq!(data.rows_with_missing_keys, Some(vec![]));
+
+        data.set_completeness(Completeness::new(["address.geo".to_string()], true));
+        let missing = data.missing_keys_by_row();
+        let rows: Vec<(usize, Vec<String>, Vec<String>)> = missing
+            .into_iter()
+            .map(|m| (m.row, m.missing, m.null))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                (2, vec![], vec!["address".to_string()]),
+                (
+                    3,
+                    vec!["address.city".to_string(), "address.geo.lat".to_string()],
+                    vec!["id".to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rules_file_quality_report() {
+        use rules::RulesFormat;
+
+        let json = r#"{"keys": {
+            "id": {"required": true, "type": "int"},
+            "status": {"allowed": ["active", "inactive"]},
+            "email": {"pattern": "^[^@]+@[^@]+$", "type": ["string", "null"]},
+            "items[*].price": {"required": true, "min": 0}
+        }}"#;
+        let toml = r#"
+            [keys.id]
+            required = true
+            type = "int"
+            [keys.status]
+            allowed = ["active", "inactive"]
+            [keys.email]
+            pattern = "^[^@]+@[^@]+$"
+            type = ["string", "null"]
+            [keys."items[*].price"]
+            required = true
+            min = 0
+        "#;
+        let records = vec![
+            json!({"id": 1, "status": "active", "email": "a@b.c", "items": [{"price": 2.5}]}),
+            json!({"id": "2", "status": "gone", "email": null, "items": []}),
+            json!({"status": "inactive", "email": "nope", "items": [{"price": -1}, {}]}),
+        ];
+
+        for (text, format) in [(json, RulesFormat::Json), (toml, RulesFormat::Toml)] {
+            let rules = RuleSet::parse(text, format).unwrap();
+            let report = rules.evaluate(&records);
+            assert_eq!(report.rows, 3);
+            assert!(!report.passed());
+            let results: Vec<(&str, usize, Vec<usize>)> = report
+                .results
+                .iter()
+                .map(|r| (r.rule.as_str(), r.passed, r.failed_rows.clone()))
+                .collect();
+            assert_eq!(
+                results,
+                vec![
+                    ("email: type string|null", 3, vec![]),
+                    // The null email of row 1 is allowed by the type, so it is not matched
+                    ("email: matches /^[^@]+@[^@]+$/", 1, vec![2]),
+                    ("id: required", 2, vec![2]),
+                    ("id: type int", 1, vec![1]),
+                    ("items[].price: required", 2, vec![2]),
+                    ("items[].price: >= 0", 1, vec![2]),
+                    ("status: one of \"active\", \"inactive\"", 2, vec![1]),
+                ],
+                "{:?}",
+                format
+            );
+        }
+
+        let range = |min, max| rules::Check::Range { min, max }.to_string();
+        assert_eq!(range(None, Some(9.5)), "<= 9.5");
+        assert_eq!(range(Some(1.0), Some(9.5)), "in 1..=9.5");
+
+        let err =
+            RuleSet::parse(r#"{"keys": {"id": {"type": "uuid"}}}"#, RulesFormat::Json).unwrap_err();
+        assert_eq!(err.to_string(), "id: unknown type 'uuid'");
+        let passing = RuleSet::parse(r#"{"keys": {"id": {"required": true}}}"#, RulesFormat::Json)
+            .unwrap()
+            .evaluate(&records[..2]);
+        assert!(passing.passed());
+    }
+
+    #[test]
+    fn test_timestamp_detection_gaps_and_histogram() {
+        use chrono::TimeDelta;
+        use timestamps::{TimeFormat, detect_timestamp_keys};
+
+        let records = vec![
+            json!({"id": 1, "ts": "2025-07-22T12:00:00Z", "epoch": 1753185600, "ms": 1753185600000i64}),
+            json!({"id": 2, "ts": "2025-07-22T12:20:00+00:00", "epoch": 1753186800.5, "ms": 1753186800000i64}),
+            json!({"id": 3, "ts": "2025-07-22 12:10:00", "epoch": 1753186200, "ms": null}),
+            json!({"id": 4, "ts": "2025-07-22T15:05:00Z", "epoch": 1753196700, "ms": 1753196700000i64}),
+            json!({"id": 5, "ts": "not yet", "epoch": 1753196800, "ms": 1753196800000i64}),
+        ];
+        // One bad value in five is below the detection threshold
+        assert_eq!(
+            detect_timestamp_keys(&records[..4]),
+            vec![
+                ("epoch".to_string(), TimeFormat::EpochSeconds),
+                ("ms".to_string(), TimeFormat::EpochMillis),
+                ("ts".to_string(), TimeFormat::Iso),
+            ]
+        );
+        assert_eq!(detect_timestamp_keys(&records).len(), 2);
+
+        let timeline = Timeline::analyze(
+            &records,
+            &KeyPath::parse("ts").unwrap(),
+            TimeFormat::Iso,
+            TimeDelta::minutes(30),
+            None,
+        );
+        assert_eq!(timeline.rows, 4);
+        assert_eq!(timeline.unparsable_rows, vec![4]);
+        assert_eq!(timeline.out_of_order_rows, vec![2]);
+        assert_eq!(
+            timeline.min.unwrap().to_rfc3339(),
+            "2025-07-22T12:00:00+00:00"
+        );
+        assert_eq!(
+            timeline.max.unwrap().to_rfc3339(),
+            "2025-07-22T15:05:00+00:00"
+        );
+        assert_eq!(timeline.gaps.len(), 1);
+        assert_eq!(
+            timeline.gaps[0].from.to_rfc3339(),
+            "2025-07-22T12:20:00+00:00"
+        );
+        assert_eq!(timeline.bucket, Bucket::Minute);
+        assert_eq!(timeline.histogram.len(), 186);
+
+        let hourly = Timeline::analyze(
+            &records,
+            &KeyPath::parse("epoch").unwrap(),
+            TimeFormat::EpochSeconds,
+            TimeDelta::hours(1),
+            Some(Bucket::Hour),
+        );
+        let counts: Vec<usize> = hourly.histogram.iter().map(|(_, n)| *n).collect();
+        assert_eq!(counts, vec![3, 0, 0, 2]);
+        assert_eq!(hourly.gaps.len(), 1);
+        assert_eq!(hourly.out_of_order_rows, vec![2]);
+
+        // Years of minutes are grouped so the bins stay capped
+        let spread = vec![
+            json!({"ms": 1600000000000i64}),
+            json!({"ms": 1700000000000i64}),
+        ];
+        let capped = Timeline::analyze(
+            &spread,
+            &KeyPath::parse("ms").unwrap(),
+            TimeFormat::EpochMillis,
+            TimeDelta::days(1),
+            Some(Bucket::Minute),
+        );
+        assert!(capped.histogram.len() <= 500);
+        assert!(capped.buckets_per_bin > 1);
+        let counts: Vec<usize> = capped.histogram.iter().map(|(_, n)| *n).collect();
+        assert_eq!(counts.iter().sum::<usize>(), 2);
+        assert_eq!((counts[0], counts[counts.len() - 1]), (1, 1));
+
+        // A named key only needs most of its values to be timestamps
+        let key = KeyPath::parse("ts").unwrap();
+        assert_eq!(
+            timestamps::majority_format(&records, &key),
+            Some(TimeFormat::Iso)
+        );
+        assert_eq!(
+            timestamps::majority_format(&records, &KeyPath::parse("id").unwrap()),
+            None
+        );
+
+        // Timestamps inside arrays are one key whatever the element count
+        let nested = vec![
+            json!({"events": [{"ts": "2025-07-22T12:00:00Z"}, {"ts": "2025-07-22T12:05:00Z"}]}),
+            json!({"events": [{"ts": "2025-07-22T12:10:00Z"}]}),
+        ];
+        assert_eq!(
+            detect_timestamp_keys(&nested),
+            vec![("events[].ts".to_string(), TimeFormat::Iso)]
+        );
+        let events = Timeline::analyze(
+            &nested,
+            &KeyPath::parse("events[].ts").unwrap(),
+            TimeFormat::Iso,
+            TimeDelta::hours(1),
+            None,
+        );
+        assert_eq!(events.rows, 2);
+        assert_eq!(events.histogram.iter().map(|(_, n)| *n).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_string_pattern_profiles() {
+        use profile::StringPattern;
+
+        for (text, pattern) in [
+            (
+                "7c9e6679-7425-40de-944b-e07fc1f90ae7",
+                Some(StringPattern::Uuid),
+            ),
+            ("ann@example.com", Some(StringPattern::Email)),
+            ("https://example.com/a?b=c", Some(StringPattern::Url)),
+            ("192.168.0.1", Some(StringPattern::Ipv4)),
+            ("2001:db8::1", Some(StringPattern::Ipv6)),
+            ("2025-07-22T12:00:00Z", Some(StringPattern::IsoDate)),
+            ("-12.5", Some(StringPattern::Numeric)),
+            ("aGVsbG8gd29ybGQ=", Some(StringPattern::Base64)),
+            ("abcdefghijkl", None),
+            ("hello world", None),
+        ] {
+            assert_eq!(StringPattern::classify(text), pattern, "{}", text);
+        }
+
+        let lines = vec![
+            r#"{"id": 1, "name": "Ann", "tags": ["a"]}"#,
+            r#"{"id": 2, "name": " Bob ", "tags": []}"#,
+            r#"{"id": "7c9e6679-7425-40de-944b-e07fc1f90ae7", "name": ""}"#,
+            r#"{"id": "0b5f2a52-1f43-4c6f-9a3e-1d2b3c4d5e6f", "name": "Cy", "tags": ["42"]}"#,
+        ];
+        let reader = MemoryJsonlReader::from_strings("profile".to_string(), lines).unwrap();
+        let data = JsonlData::with_threads(reader, 3).unwrap();
+        let profiles = data.string_profiles();
+        assert_eq!(profiles, profile::profile_records(data.reader.iter()));
+
+        let id = &profiles["id"];
+        assert_eq!((id.strings, id.non_strings), (2, 2));
+        assert_eq!(id.percent(StringPattern::Uuid), 100.0);
+
+        let name = &profiles["name"];
+        assert_eq!((name.strings, name.empty, name.padded), (4, 1, 1));
+        assert_eq!((name.min_len, name.max_len), (0, 5));
+        assert_eq!(name.avg_len(), 2.5);
+        assert!(name.patterns.is_empty());
+
+        // Array elements share one path
+        assert!(!profiles.contains_key("tags[0]"));
+        assert_eq!(profiles["tags[]"].percent(StringPattern::Numeric), 50.0);
+    }
 }
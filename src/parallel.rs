@@ -0,0 +1,38 @@
+use std::{num::NonZeroUsize, thread};
+
+/// Number of threads to use when none is configured: one per available core
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Splits `items` into at most `threads` contiguous chunks and runs `work` on
+/// each chunk in its own scoped thread. `work` gets the index of the chunk's
+/// first item; the results come back in chunk order, so merging them in order
+/// gives the same outcome as a single sequential pass.
+pub fn map_chunks<T, A, F>(items: &[T], threads: usize, work: F) -> Vec<A>
+where
+    T: Sync,
+    A: Send,
+    F: Fn(usize, &[T]) -> A + Sync,
+{
+    let chunk_size = items.len().div_ceil(threads.max(1)).max(1);
+    if threads <= 1 || items.len() <= chunk_size {
+        return vec![work(0, items)];
+    }
+
+    let work = &work;
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(i, chunk)| scope.spawn(move || work(i * chunk_size, chunk)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| match handle.join() {
+                Ok(result) => result,
+                Err(panic) => std::panic::resume_unwind(panic),
+            })
+            .collect()
+    })
+}
@@ -0,0 +1,544 @@
+use num_format::{Locale, ToFormattedString};
+use parquet::{
+    basic::{Compression, LogicalType, Repetition, Type as PhysicalType},
+    data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::{
+        printer::print_schema,
+        types::{Type, TypePtr},
+    },
+};
+use serde_json::Value;
+use std::{
+    borrow::Borrow,
+    collections::BTreeMap,
+    io::{self, Write},
+    sync::Arc,
+};
+
+/// Counts of the JSON types seen at one position of the records
+#[derive(Debug, Default)]
+struct TypeStats {
+    nulls: usize,
+    bools: usize,
+    ints: usize,
+    floats: usize,
+    strings: usize,
+    objects: usize,
+    arrays: usize,
+    fields: BTreeMap<String, TypeStats>,
+    elements: Option<Box<TypeStats>>,
+}
+
+impl TypeStats {
+    fn observe(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.nulls += 1,
+            Value::Bool(_) => self.bools += 1,
+            Value::Number(n) if n.is_i64() => self.ints += 1,
+            Value::Number(_) => self.floats += 1,
+            Value::String(_) => self.strings += 1,
+            Value::Array(arr) => {
+                self.arrays += 1;
+                let elements = self.elements.get_or_insert_default();
+                for item in arr {
+                    elements.observe(item);
+                }
+            }
+            Value::Object(map) => {
+                self.objects += 1;
+                for (key, child) in map {
+                    self.fields.entry(key.clone()).or_default().observe(child);
+                }
+            }
+        }
+    }
+
+    fn observations(&self) -> usize {
+        self.nulls
+            + self.bools
+            + self.ints
+            + self.floats
+            + self.strings
+            + self.objects
+            + self.arrays
+    }
+
+    fn fields_of(&self, occurrences: usize) -> Vec<(String, Column)> {
+        self.fields
+            .iter()
+            .filter_map(|(name, stats)| Some((name.clone(), stats.choose(occurrences)?)))
+            .collect()
+    }
+
+    /// Picks the column type held by most values; `occurrences` is how often
+    /// the enclosing object (or list) was present, to decide nullability.
+    fn choose(&self, occurrences: usize) -> Option<Column> {
+        let numbers = self.ints + self.floats;
+        let candidates = [
+            (self.strings, ColumnKind::String),
+            (
+                numbers,
+                if self.floats > 0 {
+                    ColumnKind::Double
+                } else {
+                    ColumnKind::Int
+                },
+            ),
+            (self.bools, ColumnKind::Bool),
+            (self.objects, ColumnKind::Struct(Vec::new())),
+            (
+                self.arrays,
+                ColumnKind::List(Box::new(Column::nullable_string())),
+            ),
+        ];
+        let mut best: Option<(usize, ColumnKind)> = None;
+        for (count, kind) in candidates {
+            if count > best.as_ref().map_or(0, |(c, _)| *c) {
+                best = Some((count, kind));
+            }
+        }
+        let Some((_, kind)) = best else {
+            // Only nulls: keep the key as an all-null column
+            return Some(Column::nullable_string());
+        };
+
+        let (kind, coercible) = match kind {
+            ColumnKind::String => (kind, self.strings + numbers + self.bools),
+            ColumnKind::Double => (kind, numbers),
+            ColumnKind::Int => (kind, self.ints),
+            ColumnKind::Bool => (kind, self.bools),
+            ColumnKind::Struct(_) => {
+                let fields = self.fields_of(self.objects);
+                // Parquet has no empty groups; objects that are always `{}` are dropped
+                if fields.is_empty() {
+                    return None;
+                }
+                (ColumnKind::Struct(fields), self.objects)
+            }
+            ColumnKind::List(_) => {
+                let element = self
+                    .elements
+                    .as_ref()
+                    .and_then(|e| e.choose(e.observations()))
+                    .unwrap_or_else(Column::nullable_string);
+                (ColumnKind::List(Box::new(element)), self.arrays)
+            }
+        };
+        Some(Column {
+            kind,
+            optional: coercible < occurrences,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnKind {
+    Bool,
+    Int,
+    Double,
+    String,
+    Struct(Vec<(String, Column)>),
+    List(Box<Column>),
+}
+
+/// Schema node: a Parquet column or group derived from the key analysis
+#[derive(Debug, Clone, PartialEq)]
+struct Column {
+    kind: ColumnKind,
+    optional: bool,
+}
+
+impl Column {
+    fn nullable_string() -> Self {
+        Column {
+            kind: ColumnKind::String,
+            optional: true,
+        }
+    }
+
+    fn leaf_count(&self) -> usize {
+        match &self.kind {
+            ColumnKind::Struct(fields) => fields.iter().map(|(_, f)| f.leaf_count()).sum(),
+            ColumnKind::List(element) => element.leaf_count(),
+            _ => 1,
+        }
+    }
+
+    fn parquet_type(&self, name: &str) -> parquet::errors::Result<TypePtr> {
+        let repetition = if self.optional {
+            Repetition::OPTIONAL
+        } else {
+            Repetition::REQUIRED
+        };
+        let primitive = |physical: PhysicalType| {
+            Type::primitive_type_builder(name, physical).with_repetition(repetition)
+        };
+        let built = match &self.kind {
+            ColumnKind::Bool => primitive(PhysicalType::BOOLEAN).build()?,
+            ColumnKind::Int => primitive(PhysicalType::INT64).build()?,
+            ColumnKind::Double => primitive(PhysicalType::DOUBLE).build()?,
+            ColumnKind::String => primitive(PhysicalType::BYTE_ARRAY)
+                .with_logical_type(Some(LogicalType::String))
+                .build()?,
+            ColumnKind::Struct(fields) => Type::group_type_builder(name)
+                .with_repetition(repetition)
+                .with_fields(
+                    fields
+                        .iter()
+                        .map(|(field_name, field)| field.parquet_type(field_name))
+                        .collect::<parquet::errors::Result<_>>()?,
+                )
+                .build()?,
+            // The standard three-level list layout
+            ColumnKind::List(element) => Type::group_type_builder(name)
+                .with_repetition(repetition)
+                .with_logical_type(Some(LogicalType::List))
+                .with_fields(vec![Arc::new(
+                    Type::group_type_builder("list")
+                        .with_repetition(Repetition::REPEATED)
+                        .with_fields(vec![element.parquet_type("element")?])
+                        .build()?,
+                )])
+                .build()?,
+        };
+        Ok(Arc::new(built))
+    }
+}
+
+/// A value that did not fit the type chosen for its column, written as null
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoercionError {
+    pub row: usize,
+    pub path: String,
+    /// JSON type of the value
+    pub found: &'static str,
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[derive(Debug)]
+enum LeafValues {
+    Bool(Vec<bool>),
+    Int(Vec<i64>),
+    Double(Vec<f64>),
+    Bytes(Vec<ByteArray>),
+}
+
+/// Values with definition and repetition levels of one leaf column in a row group
+#[derive(Debug)]
+struct LeafBuffer {
+    values: LeafValues,
+    def: Vec<i16>,
+    rep: Vec<i16>,
+}
+
+impl LeafBuffer {
+    fn for_column(column: &Column, out: &mut Vec<LeafBuffer>) {
+        let values = match &column.kind {
+            ColumnKind::Struct(fields) => {
+                for (_, field) in fields {
+                    Self::for_column(field, out);
+                }
+                return;
+            }
+            ColumnKind::List(element) => return Self::for_column(element, out),
+            ColumnKind::Bool => LeafValues::Bool(Vec::new()),
+            ColumnKind::Int => LeafValues::Int(Vec::new()),
+            ColumnKind::Double => LeafValues::Double(Vec::new()),
+            ColumnKind::String => LeafValues::Bytes(Vec::new()),
+        };
+        out.push(LeafBuffer {
+            values,
+            def: Vec::new(),
+            rep: Vec::new(),
+        });
+    }
+
+    /// Stores a scalar if it can be coerced to the column type.
+    fn push_value(&mut self, value: &Value) -> bool {
+        match (&mut self.values, value) {
+            (LeafValues::Bool(values), Value::Bool(b)) => values.push(*b),
+            (LeafValues::Int(values), Value::Number(n)) if n.is_i64() => {
+                values.push(n.as_i64().unwrap_or_default())
+            }
+            (LeafValues::Double(values), Value::Number(n)) => {
+                values.push(n.as_f64().unwrap_or_default())
+            }
+            (LeafValues::Bytes(values), Value::String(s)) => {
+                values.push(ByteArray::from(s.as_bytes().to_vec()))
+            }
+            (LeafValues::Bytes(values), Value::Number(_) | Value::Bool(_)) => {
+                values.push(ByteArray::from(value.to_string().into_bytes()))
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Where a value lands in the Dremel encoding
+#[derive(Debug, Clone, Copy)]
+struct Levels {
+    def: i16,
+    rep: i16,
+    /// Number of repeated ancestors
+    depth: i16,
+}
+
+struct Shredder<'a> {
+    row: usize,
+    errors: &'a mut Vec<CoercionError>,
+}
+
+impl Shredder<'_> {
+    fn nulls(leaves: &mut [LeafBuffer], levels: Levels) {
+        for leaf in leaves {
+            leaf.def.push(levels.def);
+            leaf.rep.push(levels.rep);
+        }
+    }
+
+    fn mismatch(&mut self, path: &str, value: &Value) {
+        self.errors.push(CoercionError {
+            row: self.row,
+            path: path.to_string(),
+            found: json_type(value),
+        });
+    }
+
+    fn shred(
+        &mut self,
+        column: &Column,
+        value: Option<&Value>,
+        levels: Levels,
+        leaves: &mut [LeafBuffer],
+        path: &str,
+    ) {
+        let Some(value) = value.filter(|v| !v.is_null()) else {
+            return Self::nulls(leaves, levels);
+        };
+        let present = Levels {
+            def: levels.def + i16::from(column.optional),
+            ..levels
+        };
+
+        match (&column.kind, value) {
+            (ColumnKind::Struct(fields), Value::Object(map)) => {
+                let mut start = 0;
+                for (name, field) in fields {
+                    let end = start + field.leaf_count();
+                    let field_path = format!("{}.{}", path, name);
+                    let field_path = field_path.trim_start_matches('.');
+                    self.shred(
+                        field,
+                        map.get(name),
+                        present,
+                        &mut leaves[start..end],
+                        field_path,
+                    );
+                    start = end;
+                }
+            }
+            (ColumnKind::List(_), Value::Array(arr)) if arr.is_empty() => {
+                Self::nulls(leaves, present)
+            }
+            (ColumnKind::List(element), Value::Array(arr)) => {
+                let depth = levels.depth + 1;
+                for (i, item) in arr.iter().enumerate() {
+                    let item_levels = Levels {
+                        def: present.def + 1,
+                        rep: if i == 0 { levels.rep } else { depth },
+                        depth,
+                    };
+                    self.shred(
+                        element,
+                        Some(item),
+                        item_levels,
+                        leaves,
+                        &format!("{}[{}]", path, i),
+                    );
+                }
+            }
+            (ColumnKind::Struct(_) | ColumnKind::List(_), _) => {
+                self.mismatch(path, value);
+                Self::nulls(leaves, levels);
+            }
+            (_, _) => {
+                let leaf = &mut leaves[0];
+                if leaf.push_value(value) {
+                    leaf.def.push(present.def);
+                    leaf.rep.push(present.rep);
+                } else {
+                    self.mismatch(path, value);
+                    Self::nulls(leaves, levels);
+                }
+            }
+        }
+    }
+}
+
+/// Writes records to Parquet with a schema derived from the values seen at
+/// each key path: struct columns for nested objects, list columns for
+/// arrays, the majority type for scalars (ints widen to doubles), and
+/// nullable columns for keys missing, null or mistyped in some rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParquetExport {
+    columns: Vec<(String, Column)>,
+    pub row_group_size: usize,
+}
+
+/// Outcome of a Parquet export
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParquetSummary {
+    pub rows: usize,
+    pub row_groups: usize,
+    /// Values written as null because they did not fit their column type
+    pub errors: Vec<CoercionError>,
+}
+
+impl ParquetExport {
+    pub fn infer<I>(records: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+    {
+        let mut stats = TypeStats::default();
+        let mut rows = 0;
+        for record in records {
+            stats.observe(record.borrow());
+            rows += 1;
+        }
+        Self {
+            columns: stats.fields_of(rows),
+            row_group_size: 100_000,
+        }
+    }
+
+    pub fn schema(&self) -> io::Result<TypePtr> {
+        let fields = self
+            .columns
+            .iter()
+            .map(|(name, column)| column.parquet_type(name))
+            .collect::<parquet::errors::Result<Vec<_>>>()
+            .map_err(io::Error::other)?;
+        Type::group_type_builder("schema")
+            .with_fields(fields)
+            .build()
+            .map(Arc::new)
+            .map_err(io::Error::other)
+    }
+
+    /// Writes `records` in row groups of `row_group_size`, holding one group at a time.
+    pub fn write<I, W>(&self, records: I, writer: W) -> io::Result<ParquetSummary>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+        W: Write + Send,
+    {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut file = SerializedFileWriter::new(writer, self.schema()?, Arc::new(properties))
+            .map_err(io::Error::other)?;
+
+        let mut summary = ParquetSummary {
+            rows: 0,
+            row_groups: 0,
+            errors: Vec::new(),
+        };
+        let root = Column {
+            kind: ColumnKind::Struct(self.columns.clone()),
+            optional: false,
+        };
+
+        let mut records = records.into_iter().peekable();
+        while records.peek().is_some() {
+            let chunk: Vec<I::Item> = records.by_ref().take(self.row_group_size.max(1)).collect();
+            let mut leaves = Vec::new();
+            LeafBuffer::for_column(&root, &mut leaves);
+            for record in &chunk {
+                let mut shredder = Shredder {
+                    row: summary.rows,
+                    errors: &mut summary.errors,
+                };
+                let levels = Levels {
+                    def: 0,
+                    rep: 0,
+                    depth: 0,
+                };
+                shredder.shred(&root, Some(record.borrow()), levels, &mut leaves, "");
+                summary.rows += 1;
+            }
+            self.write_row_group(&mut file, &leaves)
+                .map_err(io::Error::other)?;
+            summary.row_groups += 1;
+        }
+
+        file.close().map_err(io::Error::other)?;
+        Ok(summary)
+    }
+
+    fn write_row_group<W: Write + Send>(
+        &self,
+        file: &mut SerializedFileWriter<W>,
+        leaves: &[LeafBuffer],
+    ) -> parquet::errors::Result<()> {
+        let mut row_group = file.next_row_group()?;
+        let mut leaves = leaves.iter();
+        while let Some(mut column) = row_group.next_column()? {
+            let Some(leaf) = leaves.next() else {
+                break;
+            };
+            let (def, rep) = (Some(leaf.def.as_slice()), Some(leaf.rep.as_slice()));
+            match &leaf.values {
+                LeafValues::Bool(values) => {
+                    column.typed::<BoolType>().write_batch(values, def, rep)?
+                }
+                LeafValues::Int(values) => {
+                    column.typed::<Int64Type>().write_batch(values, def, rep)?
+                }
+                LeafValues::Double(values) => {
+                    column.typed::<DoubleType>().write_batch(values, def, rep)?
+                }
+                LeafValues::Bytes(values) => column
+                    .typed::<ByteArrayType>()
+                    .write_batch(values, def, rep)?,
+            };
+            column.close()?;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+}
+
+impl ParquetSummary {
+    pub fn show_report(&self, export: &ParquetExport, destination: &str) {
+        println!("===============================");
+        println!(
+            "Wrote {} rows in {} row groups to {}",
+            self.rows.to_formatted_string(&Locale::en),
+            self.row_groups,
+            destination
+        );
+        if let Ok(schema) = export.schema() {
+            print_schema(&mut io::stdout(), &schema);
+        }
+        println!(
+            "Values that could not be coerced to their column type (written as null): {}",
+            self.errors.len().to_formatted_string(&Locale::en)
+        );
+        for error in &self.errors {
+            println!("\trow {}: {} is {}", error.row, error.path, error.found);
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Error raised when a key path expression cannot be parsed
+#[derive(Debug)]
+pub struct PathError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid key path '{}': {}", self.path, self.message)
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// One step of a key path: an object key, an array index, or a wildcard
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+    /// `*` (any object key) or `[*]`/`[]` (any array index)
+    Wildcard,
+}
+
+/// Report path of `key` in the object at `prefix` (`user` and `name` give `user.name`)
+pub fn child_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Report path shared by all elements of the array at `prefix`: `tags[]`
+pub fn element_path(prefix: &str) -> String {
+    format!("{}[]", prefix)
+}
+
+/// `items[3].id` becomes `items[].id`, the path shared by every element.
+pub fn without_indices(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => {
+                in_index = true;
+                out.push('[');
+            }
+            ']' => {
+                in_index = false;
+                out.push(']');
+            }
+            _ if in_index => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Calls `visit` with every value nested in `value` and its report path,
+/// parents before children. The elements of an array all share one path
+/// (`{"tags": ["a", "b"]}` visits `tags`, then `tags[]` twice), so the
+/// reports count a key once however many elements have it.
+pub fn walk_paths<'a, F>(value: &'a Value, visit: &mut F)
+where
+    F: FnMut(&str, &'a Value),
+{
+    walk_below(value, "", visit);
+}
+
+fn walk_below<'a, F>(value: &'a Value, prefix: &str, visit: &mut F)
+where
+    F: FnMut(&str, &'a Value),
+{
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = child_path(prefix, key);
+                visit(&path, child);
+                walk_below(child, &path, visit);
+            }
+        }
+        Value::Array(items) => {
+            let path = element_path(prefix);
+            for item in items {
+                visit(&path, item);
+                walk_below(item, &path, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A key path in the dotted/indexed notation used by the key reports,
+/// e.g. `user.name`, `tags[0]` or `items[2].id`; a report path such as
+/// `items[].id` parses with `[]` as a wildcard over the elements.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KeyPath {
+    segments: Vec<Segment>,
+}
+
+impl KeyPath {
+    pub fn parse(path: &str) -> Result<Self, PathError> {
+        let err = |message: &str| PathError {
+            path: path.to_string(),
+            message: message.to_string(),
+        };
+
+        if path.trim().is_empty() {
+            return Err(err("path is empty"));
+        }
+
+        let mut segments = Vec::new();
+        for (i, part) in path.split('.').enumerate() {
+            let (name, mut rest) = match part.find('[') {
+                Some(pos) => (&part[..pos], &part[pos..]),
+                None => (part, ""),
+            };
+
+            if name.is_empty() {
+                // Only a leading `[n]` (a top-level array) may omit the key name
+                if i > 0 || rest.is_empty() {
+                    return Err(err("empty key segment"));
+                }
+            } else if name == "*" {
+                segments.push(Segment::Wildcard);
+            } else {
+                segments.push(Segment::Key(name.to_string()));
+            }
+
+            while !rest.is_empty() {
+                let close = rest.find(']').ok_or_else(|| err("unclosed '['"))?;
+                let inner = &rest[1..close];
+                if inner == "*" || inner.is_empty() {
+                    segments.push(Segment::Wildcard);
+                } else {
+                    let index = inner
+                        .parse::<usize>()
+                        .map_err(|_| err("array index must be a number or '*'"))?;
+                    segments.push(Segment::Index(index));
+                }
+                rest = &rest[close + 1..];
+                if !rest.is_empty() && !rest.starts_with('[') {
+                    return Err(err("unexpected characters after ']'"));
+                }
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Parses a path naming at most one value, for lookups that take a single
+    /// value per record (grouping, sorting, join and partition keys).
+    pub fn parse_single(path: &str) -> Result<Self, PathError> {
+        let parsed = Self::parse(path)?;
+        if parsed.has_wildcard() {
+            return Err(PathError {
+                path: path.to_string(),
+                message: "a wildcard matches several values; name one element by index".to_string(),
+            });
+        }
+        Ok(parsed)
+    }
+
+    pub fn from_segments(segments: Vec<Segment>) -> Self {
+        Self { segments }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.contains(&Segment::Wildcard)
+    }
+
+    /// Returns the value at this path. Wildcard segments never match here; use
+    /// [`KeyPath::expand`] first to resolve them against a record.
+    pub fn get<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        let mut current = value;
+        for segment in &self.segments {
+            current = match (segment, current) {
+                (Segment::Key(k), Value::Object(map)) => map.get(k)?,
+                (Segment::Index(i), Value::Array(arr)) => arr.get(*i)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Returns every value at this path: each match of its wildcards, or the
+    /// single value [`KeyPath::get`] finds.
+    pub fn get_all<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        if self.has_wildcard() {
+            self.expand(value)
+                .iter()
+                .filter_map(|(concrete, _)| concrete.get(value))
+                .collect()
+        } else {
+            self.get(value).into_iter().collect()
+        }
+    }
+
+    pub fn get_mut<'a>(&self, value: &'a mut Value) -> Option<&'a mut Value> {
+        let mut current = value;
+        for segment in &self.segments {
+            current = match (segment, current) {
+                (Segment::Key(k), Value::Object(map)) => map.get_mut(k)?,
+                (Segment::Index(i), Value::Array(arr)) => arr.get_mut(*i)?,
+                _ => return None,
+            };
+        }
+        Some(current)
+    }
+
+    /// Removes and returns the value at this path. Array elements are removed,
+    /// shifting later elements down.
+    pub fn remove(&self, value: &mut Value) -> Option<Value> {
+        let (last, parent) = self.segments.split_last()?;
+        let parent = Self::from_segments(parent.to_vec()).get_mut(value)?;
+        match (last, parent) {
+            (Segment::Key(k), Value::Object(map)) => map.remove(k),
+            (Segment::Index(i), Value::Array(arr)) if *i < arr.len() => Some(arr.remove(*i)),
+            _ => None,
+        }
+    }
+
+    /// Stores `new_value` at this path, creating intermediate objects as needed.
+    /// Returns `false` if an existing non-object value is in the way.
+    pub fn insert(&self, value: &mut Value, new_value: Value) -> bool {
+        let Some((last, parents)) = self.segments.split_last() else {
+            *value = new_value;
+            return true;
+        };
+
+        let mut current = value;
+        for segment in parents {
+            current = match segment {
+                Segment::Key(k) => {
+                    if current.is_null() {
+                        *current = Value::Object(Map::new());
+                    }
+                    match current {
+                        Value::Object(map) => map
+                            .entry(k.clone())
+                            .or_insert_with(|| Value::Object(Map::new())),
+                        _ => return false,
+                    }
+                }
+                Segment::Index(i) => match current {
+                    Value::Array(arr) if *i < arr.len() => &mut arr[*i],
+                    _ => return false,
+                },
+                Segment::Wildcard => return false,
+            };
+        }
+
+        match (last, current) {
+            (Segment::Key(k), current) => {
+                if current.is_null() {
+                    *current = Value::Object(Map::new());
+                }
+                match current {
+                    Value::Object(map) => {
+                        map.insert(k.clone(), new_value);
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            (Segment::Index(i), Value::Array(arr)) if *i < arr.len() => {
+                arr[*i] = new_value;
+                true
+            }
+            (Segment::Index(i), Value::Array(arr)) if *i == arr.len() => {
+                arr.push(new_value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Resolves wildcards against `value`, returning every concrete path that
+    /// exists in it together with the keys/indices each wildcard matched.
+    pub fn expand(&self, value: &Value) -> Vec<(KeyPath, Vec<Segment>)> {
+        let mut matches = Vec::new();
+        Self::expand_into(&self.segments, value, Vec::new(), Vec::new(), &mut matches);
+        matches
+    }
+
+    fn expand_into(
+        remaining: &[Segment],
+        value: &Value,
+        prefix: Vec<Segment>,
+        captures: Vec<Segment>,
+        matches: &mut Vec<(KeyPath, Vec<Segment>)>,
+    ) {
+        let Some((segment, rest)) = remaining.split_first() else {
+            matches.push((KeyPath::from_segments(prefix), captures));
+            return;
+        };
+
+        let mut descend = |next: Segment, child: &Value, captured: Option<Segment>| {
+            let mut prefix = prefix.clone();
+            prefix.push(next);
+            let mut captures = captures.clone();
+            captures.extend(captured);
+            Self::expand_into(rest, child, prefix, captures, matches);
+        };
+
+        match (segment, value) {
+            (Segment::Key(k), Value::Object(map)) => {
+                if let Some(child) = map.get(k) {
+                    descend(segment.clone(), child, None);
+                }
+            }
+            (Segment::Index(i), Value::Array(arr)) => {
+                if let Some(child) = arr.get(*i) {
+                    descend(segment.clone(), child, None);
+                }
+            }
+            (Segment::Wildcard, Value::Object(map)) => {
+                for (k, child) in map {
+                    let seg = Segment::Key(k.clone());
+                    descend(seg.clone(), child, Some(seg));
+                }
+            }
+            (Segment::Wildcard, Value::Array(arr)) => {
+                for (i, child) in arr.iter().enumerate() {
+                    let seg = Segment::Index(i);
+                    descend(seg.clone(), child, Some(seg));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl fmt::Display for KeyPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Key(k) if i == 0 => write!(f, "{}", k)?,
+                Segment::Key(k) => write!(f, ".{}", k)?,
+                Segment::Index(idx) => write!(f, "[{}]", idx)?,
+                Segment::Wildcard => write!(f, "[]")?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,256 @@
+use crate::path::walk_paths;
+use crate::timestamps::parse_iso;
+use num_format::{Locale, ToFormattedString};
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::OnceLock,
+};
+
+/// Shorter strings are never taken for base64, so that short words are not
+const MIN_BASE64_LEN: usize = 12;
+
+/// Recognized shape of a string value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StringPattern {
+    Uuid,
+    Email,
+    Url,
+    Ipv4,
+    Ipv6,
+    /// ISO 8601 dates and date-times
+    IsoDate,
+    /// Integers or decimals written as a string
+    Numeric,
+    Base64,
+}
+
+impl fmt::Display for StringPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            StringPattern::Uuid => "uuid",
+            StringPattern::Email => "email",
+            StringPattern::Url => "url",
+            StringPattern::Ipv4 => "ipv4",
+            StringPattern::Ipv6 => "ipv6",
+            StringPattern::IsoDate => "iso-date",
+            StringPattern::Numeric => "numeric",
+            StringPattern::Base64 => "base64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("built-in pattern is valid"))
+}
+
+fn is_base64(s: &str) -> bool {
+    let body = s.trim_end_matches('=');
+    s.len() >= MIN_BASE64_LEN
+        && s.len().is_multiple_of(4)
+        && s.len() - body.len() <= 2
+        && body
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/')
+        // Real encodings mix letters with digits or symbols
+        && body.bytes().any(|b| !b.is_ascii_alphabetic())
+}
+
+impl StringPattern {
+    /// The first pattern `s` matches, checked from the most to the least specific
+    pub fn classify(s: &str) -> Option<Self> {
+        static UUID: OnceLock<Regex> = OnceLock::new();
+        static EMAIL: OnceLock<Regex> = OnceLock::new();
+        static URL: OnceLock<Regex> = OnceLock::new();
+        static NUMERIC: OnceLock<Regex> = OnceLock::new();
+
+        if regex(
+            &UUID,
+            r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$",
+        )
+        .is_match(s)
+        {
+            Some(StringPattern::Uuid)
+        } else if regex(&NUMERIC, r"^[-+]?(\d+\.?\d*|\.\d+)([eE][-+]?\d+)?$").is_match(s) {
+            Some(StringPattern::Numeric)
+        } else if s.parse::<Ipv4Addr>().is_ok() {
+            Some(StringPattern::Ipv4)
+        } else if s.parse::<Ipv6Addr>().is_ok() {
+            Some(StringPattern::Ipv6)
+        } else if parse_iso(s).is_some() {
+            Some(StringPattern::IsoDate)
+        } else if regex(&EMAIL, r"^[^@\s]+@[^@\s]+\.[^@\s]+$").is_match(s) {
+            Some(StringPattern::Email)
+        } else if regex(&URL, r"^[a-zA-Z][a-zA-Z0-9+.-]*://[^\s/?#]+\S*$").is_match(s) {
+            Some(StringPattern::Url)
+        } else if is_base64(s) {
+            Some(StringPattern::Base64)
+        } else {
+            None
+        }
+    }
+}
+
+/// Statistics of the string values of one key path
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringProfile {
+    pub strings: usize,
+    /// Values of other types (null included), to spot keys changing type
+    pub non_strings: usize,
+    pub empty: usize,
+    /// Strings with leading or trailing whitespace
+    pub padded: usize,
+    /// Lengths in characters
+    pub min_len: usize,
+    pub max_len: usize,
+    pub total_len: usize,
+    pub patterns: BTreeMap<StringPattern, usize>,
+}
+
+impl StringProfile {
+    pub fn avg_len(&self) -> f64 {
+        if self.strings == 0 {
+            0.0
+        } else {
+            self.total_len as f64 / self.strings as f64
+        }
+    }
+
+    /// Share of the strings matching `pattern`, in percent
+    pub fn percent(&self, pattern: StringPattern) -> f64 {
+        let count = self.patterns.get(&pattern).copied().unwrap_or(0);
+        if self.strings == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / self.strings as f64
+        }
+    }
+
+    fn add(&mut self, value: &Value) {
+        let Value::String(s) = value else {
+            self.non_strings += 1;
+            return;
+        };
+        let len = s.chars().count();
+        self.min_len = if self.strings == 0 {
+            len
+        } else {
+            self.min_len.min(len)
+        };
+        self.max_len = self.max_len.max(len);
+        self.total_len += len;
+        self.strings += 1;
+        if s.is_empty() {
+            self.empty += 1;
+        } else if s.trim() != s {
+            self.padded += 1;
+        }
+        if let Some(pattern) = StringPattern::classify(s) {
+            *self.patterns.entry(pattern).or_insert(0) += 1;
+        }
+    }
+
+    fn merge(&mut self, other: StringProfile) {
+        if other.strings > 0 {
+            self.min_len = if self.strings == 0 {
+                other.min_len
+            } else {
+                self.min_len.min(other.min_len)
+            };
+        }
+        self.strings += other.strings;
+        self.non_strings += other.non_strings;
+        self.empty += other.empty;
+        self.padded += other.padded;
+        self.max_len = self.max_len.max(other.max_len);
+        self.total_len += other.total_len;
+        for (pattern, count) in other.patterns {
+            *self.patterns.entry(pattern).or_insert(0) += count;
+        }
+    }
+}
+
+/// Adds the scalar values of `value` to the profile of their key path;
+/// array elements share one path (`tags[]`).
+fn profile_leaves(value: &Value, profiles: &mut BTreeMap<String, StringProfile>) {
+    walk_paths(value, &mut |path, leaf| {
+        if !leaf.is_object() && !leaf.is_array() {
+            profiles.entry(path.to_string()).or_default().add(leaf);
+        }
+    });
+}
+
+/// Profiles of the scalar key paths of `records`
+pub fn profile_records<'a, I>(records: I) -> BTreeMap<String, StringProfile>
+where
+    I: IntoIterator<Item = &'a Value>,
+{
+    let mut profiles = BTreeMap::new();
+    for record in records {
+        profile_leaves(record, &mut profiles);
+    }
+    profiles
+}
+
+/// Merges per-chunk profiles computed in parallel.
+pub fn merge_profiles(
+    parts: impl IntoIterator<Item = BTreeMap<String, StringProfile>>,
+) -> BTreeMap<String, StringProfile> {
+    let mut merged: BTreeMap<String, StringProfile> = BTreeMap::new();
+    for part in parts {
+        for (path, profile) in part {
+            merged.entry(path).or_default().merge(profile);
+        }
+    }
+    merged
+}
+
+/// Prints one line per key path holding strings.
+pub fn show_string_profiles(profiles: &BTreeMap<String, StringProfile>) {
+    let profiles: Vec<(&String, &StringProfile)> =
+        profiles.iter().filter(|(_, p)| p.strings > 0).collect();
+    let width = profiles
+        .iter()
+        .map(|(path, _)| path.len())
+        .max()
+        .unwrap_or(20)
+        .max(20);
+    println!(
+        "{:<width$} {:>10} {:>10} {:>8} {:>8} {:>18}  Patterns",
+        "Key",
+        "Strings",
+        "Other",
+        "Empty",
+        "Padded",
+        "Length min/avg/max",
+        width = width
+    );
+    println!("{}", "-".repeat(width + 82));
+    for (path, profile) in profiles {
+        let patterns: Vec<String> = profile
+            .patterns
+            .keys()
+            .map(|pattern| format!("{} {:.1}%", pattern, profile.percent(*pattern)))
+            .collect();
+        println!(
+            "\t{:<width$} {:>10} {:>10} {:>8} {:>8} {:>18}  {}",
+            path,
+            profile.strings.to_formatted_string(&Locale::en),
+            profile.non_strings.to_formatted_string(&Locale::en),
+            profile.empty.to_formatted_string(&Locale::en),
+            profile.padded.to_formatted_string(&Locale::en),
+            format!(
+                "{}/{:.1}/{}",
+                profile.min_len,
+                profile.avg_len(),
+                profile.max_len
+            ),
+            patterns.join(", "),
+            width = width
+        );
+    }
+}
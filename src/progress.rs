@@ -0,0 +1,200 @@
+use num_format::{Locale, ToFormattedString};
+use std::{
+    fmt,
+    io::{self, IsTerminal},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Rows between checks of whether a progress update is due
+const CHECK_EVERY_ROWS: usize = 4096;
+/// Minimum time between two progress updates of a stage
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Receives the progress of long-running stages; handed to readers and to
+/// `JsonlData`, which pass it on to their trackers.
+pub type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// State of a long-running stage (such as `read` or `keys`) passed to the
+/// progress callback: periodically while it runs, and once when it is done.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub stage: &'static str,
+    pub rows: usize,
+    pub bytes: u64,
+    pub total_rows: Option<usize>,
+    pub total_bytes: Option<u64>,
+    pub elapsed: Duration,
+    pub done: bool,
+}
+
+impl Progress {
+    pub fn rows_per_sec(&self) -> f64 {
+        per_second(self.rows as f64, self.elapsed)
+    }
+
+    pub fn bytes_per_sec(&self) -> f64 {
+        per_second(self.bytes as f64, self.elapsed)
+    }
+
+    /// Estimated time left, from the share of bytes (or rows) done so far
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = match (self.total_bytes, self.total_rows) {
+            (Some(total), _) if total > 0 && self.bytes > 0 => self.bytes as f64 / total as f64,
+            (_, Some(total)) if total > 0 && self.rows > 0 => self.rows as f64 / total as f64,
+            _ => return None,
+        };
+        let remaining = self.elapsed.as_secs_f64() * (1.0 - fraction.min(1.0)) / fraction;
+        Some(Duration::from_secs_f64(remaining))
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} rows",
+            self.stage,
+            self.rows.to_formatted_string(&Locale::en)
+        )?;
+        if self.bytes > 0 {
+            write!(f, " ({})", format_bytes(self.bytes as f64))?;
+        }
+        if self.done {
+            write!(f, " in {:.2}s", self.elapsed.as_secs_f64())?;
+        }
+        write!(
+            f,
+            ", {} rows/s",
+            (self.rows_per_sec() as u64).to_formatted_string(&Locale::en)
+        )?;
+        if self.bytes > 0 {
+            write!(f, ", {}/s", format_bytes(self.bytes_per_sec()))?;
+        }
+        if !self.done
+            && let Some(eta) = self.eta()
+        {
+            let secs = eta.as_secs();
+            write!(f, ", ETA {}:{:02}", secs / 60, secs % 60)?;
+        }
+        Ok(())
+    }
+}
+
+fn per_second(amount: f64, elapsed: Duration) -> f64 {
+    let secs = elapsed.as_secs_f64();
+    if secs > 0.0 { amount / secs } else { 0.0 }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes as u64)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// A callback that draws a progress line on stderr when it is a terminal,
+/// and prints a throughput summary once the input has been read.
+pub fn report_to_stderr() -> ProgressCallback {
+    let interactive = io::stderr().is_terminal();
+    Arc::new(move |progress: &Progress| {
+        if !progress.done {
+            if interactive {
+                eprint!("\r\x1b[K{}", progress);
+            }
+            return;
+        }
+        if interactive {
+            eprint!("\r\x1b[K");
+        }
+        if progress.stage == "read" {
+            eprintln!("{}", progress);
+        }
+    })
+}
+
+/// Counts rows and bytes of one stage and reports them to its callback, if
+/// it has one. Safe to share between threads.
+pub struct ProgressTracker {
+    callback: Option<ProgressCallback>,
+    stage: &'static str,
+    total_rows: Option<usize>,
+    total_bytes: Option<u64>,
+    rows: AtomicUsize,
+    bytes: AtomicU64,
+    started: Instant,
+    last_update: Mutex<Instant>,
+}
+
+impl ProgressTracker {
+    pub fn new(stage: &'static str, total_rows: Option<usize>, total_bytes: Option<u64>) -> Self {
+        let now = Instant::now();
+        Self {
+            callback: None,
+            stage,
+            total_rows,
+            total_bytes,
+            rows: AtomicUsize::new(0),
+            bytes: AtomicU64::new(0),
+            started: now,
+            last_update: Mutex::new(now),
+        }
+    }
+
+    /// Reports to `callback`; without one the tracker only counts.
+    pub fn with_callback(mut self, callback: Option<ProgressCallback>) -> Self {
+        self.callback = callback;
+        self
+    }
+
+    pub fn add(&self, rows: usize, bytes: u64) {
+        let before = self.rows.fetch_add(rows, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        if (before + rows) / CHECK_EVERY_ROWS != before / CHECK_EVERY_ROWS {
+            self.update();
+        }
+    }
+
+    fn update(&self) {
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        // Another thread already reporting is as good as reporting ourselves
+        if let Ok(mut last) = self.last_update.try_lock()
+            && last.elapsed() >= UPDATE_INTERVAL
+        {
+            *last = Instant::now();
+            callback(&self.snapshot(false));
+        }
+    }
+
+    /// Reports the final state of the stage.
+    pub fn finish(&self) {
+        if let Some(callback) = &self.callback {
+            callback(&self.snapshot(true));
+        }
+    }
+
+    pub fn snapshot(&self, done: bool) -> Progress {
+        Progress {
+            stage: self.stage,
+            rows: self.rows.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            total_rows: self.total_rows,
+            total_bytes: self.total_bytes,
+            elapsed: self.started.elapsed(),
+            done,
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use crate::path::{KeyPath, PathError, Segment};
+use serde_json::{Map, Value};
+use std::borrow::Borrow;
+
+/// Keeps only selected fields of each record.
+///
+/// Fields are key paths in report notation (`user.name`, `items[*].id`) or
+/// simple JSONPath expressions (`$.user.name`, `$['user']['name']`, `$.items[*].id`).
+/// Fields missing from a record are left out of its projection.
+#[derive(Debug, Clone)]
+pub struct Projection {
+    paths: Vec<KeyPath>,
+    flatten: bool,
+}
+
+impl Projection {
+    /// With `flatten`, the output uses top-level dotted keys instead of nesting.
+    pub fn parse<S: AsRef<str>>(fields: &[S], flatten: bool) -> Result<Self, PathError> {
+        let paths = fields
+            .iter()
+            .map(|f| parse_field(f.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { paths, flatten })
+    }
+
+    pub fn apply(&self, record: &Value) -> Value {
+        let mut projected = Value::Object(Map::new());
+
+        for path in &self.paths {
+            for (concrete, _) in path.expand(record) {
+                let Some(value) = concrete.get(record) else {
+                    continue;
+                };
+                if let Value::Object(out) = &mut projected
+                    && self.flatten
+                {
+                    flatten_into(&concrete.to_string(), value, out);
+                } else {
+                    set_path(&mut projected, concrete.segments(), value.clone());
+                }
+            }
+        }
+
+        projected
+    }
+
+    /// Streams the projection of every record of a reader.
+    pub fn project<'a, I>(&'a self, records: I) -> impl Iterator<Item = Value> + 'a
+    where
+        I: IntoIterator + 'a,
+        I::Item: Borrow<Value>,
+    {
+        records
+            .into_iter()
+            .map(|record| self.apply(record.borrow()))
+    }
+}
+
+/// Converts a JSONPath expression to report notation; other input is used as is.
+fn parse_field(field: &str) -> Result<KeyPath, PathError> {
+    let Some(rest) = field.trim().strip_prefix('$') else {
+        return KeyPath::parse(field.trim());
+    };
+
+    let mut dotted = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' if chars.peek() == Some(&'.') => {
+                return Err(PathError {
+                    path: field.to_string(),
+                    message: "recursive descent ('..') is not supported".to_string(),
+                });
+            }
+            '[' if matches!(chars.peek(), Some('\'') | Some('"')) => {
+                let quote = chars.next().unwrap_or('\'');
+                dotted.push('.');
+                for ch in chars.by_ref() {
+                    if ch == quote {
+                        break;
+                    }
+                    dotted.push(ch);
+                }
+                if chars.next() != Some(']') {
+                    return Err(PathError {
+                        path: field.to_string(),
+                        message: "expected ']' after quoted key".to_string(),
+                    });
+                }
+            }
+            _ => dotted.push(c),
+        }
+    }
+
+    KeyPath::parse(dotted.trim_start_matches('.'))
+}
+
+/// Stores `value` under `segments`, creating objects and arrays (padded with
+/// nulls) as needed.
+fn set_path(target: &mut Value, segments: &[Segment], value: Value) {
+    let Some((first, rest)) = segments.split_first() else {
+        *target = value;
+        return;
+    };
+
+    let slot = match first {
+        Segment::Key(key) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            let Value::Object(map) = target else {
+                return;
+            };
+            map.entry(key.clone()).or_insert(Value::Null)
+        }
+        Segment::Index(index) => {
+            if !target.is_array() {
+                *target = Value::Array(Vec::new());
+            }
+            let Value::Array(arr) = target else {
+                return;
+            };
+            if arr.len() <= *index {
+                arr.resize(*index + 1, Value::Null);
+            }
+            &mut arr[*index]
+        }
+        Segment::Wildcard => return,
+    };
+
+    set_path(slot, rest, value);
+}
+
+/// Flattens nested objects and arrays into `out` using report notation keys
+/// (`user.name`, `tags[0]`). Empty objects and arrays are kept as values.
+pub fn flatten_into(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+    flatten(prefix, value, out, true);
+}
+
+/// Like [`flatten_into`], but keeps arrays whole as values.
+pub fn flatten_objects_into(prefix: &str, value: &Value, out: &mut Map<String, Value>) {
+    flatten(prefix, value, out, false);
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Map<String, Value>, arrays: bool) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let full_key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten(&full_key, child, out, arrays);
+            }
+        }
+        Value::Array(arr) if arrays && !arr.is_empty() => {
+            for (index, child) in arr.iter().enumerate() {
+                flatten(&format!("{}[{}]", prefix, index), child, out, arrays);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use crate::jsonl::JsonlReader;
+use crate::path::KeyPath;
+use regex::Regex;
+use serde_json::Value;
+use std::{cmp::Ordering, fmt};
+
+/// Error raised when a filter expression cannot be parsed
+#[derive(Debug)]
+pub struct QueryError {
+    pub expression: String,
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid filter '{}' at position {}: {}",
+            self.expression, self.position, self.message
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Matches,
+    NotMatches,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(KeyPath),
+    Compare(KeyPath, CompareOp, Value),
+    Regex(KeyPath, bool, Regex),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Path(String),
+    Literal(Value),
+    Op(CompareOp),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+/// A row filter such as `age > 30 && name =~ "^A"` or `!exists(timestamp)`.
+///
+/// Comparisons take a key path (report notation) on the left and a literal
+/// (number, string, `true`, `false` or `null`) on the right. A comparison on
+/// a path that is absent from the record never matches.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    expression: String,
+    expr: Expr,
+}
+
+impl Filter {
+    pub fn parse(expression: &str) -> Result<Self, QueryError> {
+        let tokens = tokenize(expression)?;
+        let mut parser = Parser {
+            expression,
+            tokens,
+            pos: 0,
+        };
+        let expr = parser.parse_or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Self {
+            expression: expression.to_string(),
+            expr,
+        })
+    }
+
+    pub fn expression(&self) -> &str {
+        &self.expression
+    }
+
+    pub fn matches(&self, record: &Value) -> bool {
+        eval(&self.expr, record)
+    }
+
+    /// Iterates over the matching records of any reader with their row numbers.
+    pub fn select<'a, R: JsonlReader>(
+        &'a self,
+        reader: &'a R,
+    ) -> impl Iterator<Item = (usize, &'a Value)> + 'a {
+        reader
+            .iter()
+            .enumerate()
+            .filter(move |(_, record)| self.matches(record))
+    }
+
+    pub fn count<R: JsonlReader>(&self, reader: &R) -> usize {
+        self.select(reader).count()
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+fn eval(expr: &Expr, record: &Value) -> bool {
+    match expr {
+        Expr::And(a, b) => eval(a, record) && eval(b, record),
+        Expr::Or(a, b) => eval(a, record) || eval(b, record),
+        Expr::Not(e) => !eval(e, record),
+        // A wildcard path (`items[].id`) matches when any of its values does
+        Expr::Exists(path) => !path.get_all(record).is_empty(),
+        Expr::Compare(path, op, literal) => path
+            .get_all(record)
+            .into_iter()
+            .any(|value| compare(value, *op, literal)),
+        Expr::Regex(path, negate, regex) => {
+            path.get_all(record).into_iter().any(|value| match value {
+                Value::String(s) => regex.is_match(s) != *negate,
+                Value::Number(_) | Value::Bool(_) => regex.is_match(&value.to_string()) != *negate,
+                _ => false,
+            })
+        }
+    }
+}
+
+fn compare(value: &Value, op: CompareOp, literal: &Value) -> bool {
+    let ordering = match (value, literal) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .zip(b.as_f64())
+            .and_then(|(a, b)| a.partial_cmp(&b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        _ => None,
+    };
+
+    match (op, ordering) {
+        (CompareOp::Eq, Some(o)) => o == Ordering::Equal,
+        (CompareOp::Ne, Some(o)) => o != Ordering::Equal,
+        // Values of different types are never equal
+        (CompareOp::Ne, None) => true,
+        (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+        (CompareOp::Ge, Some(o)) => o != Ordering::Less,
+        (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+        (CompareOp::Le, Some(o)) => o != Ordering::Greater,
+        _ => false,
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let err = |position: usize, message: &str| QueryError {
+        expression: expression.to_string(),
+        position,
+        message: message.to_string(),
+    };
+
+    let chars: Vec<(usize, char)> = expression.char_indices().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (pos, c) = chars[i];
+        let next = chars.get(i + 1).map(|&(_, c)| c);
+
+        let two_char_token = match (c, next) {
+            ('&', Some('&')) => Some(Token::And),
+            ('|', Some('|')) => Some(Token::Or),
+            ('=', Some('=')) => Some(Token::Op(CompareOp::Eq)),
+            ('!', Some('=')) => Some(Token::Op(CompareOp::Ne)),
+            ('>', Some('=')) => Some(Token::Op(CompareOp::Ge)),
+            ('<', Some('=')) => Some(Token::Op(CompareOp::Le)),
+            ('=', Some('~')) => Some(Token::Op(CompareOp::Matches)),
+            ('!', Some('~')) => Some(Token::Op(CompareOp::NotMatches)),
+            _ => None,
+        };
+        if let Some(token) = two_char_token {
+            tokens.push((pos, token));
+            i += 2;
+            continue;
+        }
+
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '(' | ')' | '!' | '>' | '<' => {
+                let token = match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    '!' => Token::Not,
+                    '>' => Token::Op(CompareOp::Gt),
+                    _ => Token::Op(CompareOp::Lt),
+                };
+                tokens.push((pos, token));
+                i += 1;
+            }
+            '"' | '\'' => {
+                let quote = c;
+                let mut s = String::new();
+                i += 1;
+                loop {
+                    let Some(&(_, ch)) = chars.get(i) else {
+                        return Err(err(pos, "unterminated string"));
+                    };
+                    i += 1;
+                    match ch {
+                        '\\' => {
+                            let Some(&(_, escaped)) = chars.get(i) else {
+                                return Err(err(pos, "unterminated string"));
+                            };
+                            // Keep regex escapes such as `\d` intact
+                            if escaped != quote && escaped != '\\' {
+                                s.push('\\');
+                            }
+                            s.push(escaped);
+                            i += 1;
+                        }
+                        ch if ch == quote => break,
+                        ch => s.push(ch),
+                    }
+                }
+                tokens.push((pos, Token::Literal(Value::String(s))));
+            }
+            c if c.is_ascii_digit() || (c == '-' && next.is_some_and(|n| n.is_ascii_digit())) => {
+                let start = i;
+                i += 1;
+                while let Some(&(_, ch)) = chars.get(i) {
+                    if ch.is_ascii_digit() || matches!(ch, '.' | 'e' | 'E' | '+' | '-') {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let text: String = chars[start..i].iter().map(|&(_, c)| c).collect();
+                let number: Value =
+                    serde_json::from_str(&text).map_err(|_| err(pos, "invalid number"))?;
+                tokens.push((pos, Token::Literal(number)));
+            }
+            c if c.is_alphanumeric() || matches!(c, '_' | '[' | '*') => {
+                let start = i;
+                while let Some(&(_, ch)) = chars.get(i) {
+                    if ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.' | '[' | ']' | '*') {
+                        i += 1;
+                    } else {
+                        break;
+                    }
+                }
+                let word: String = chars[start..i].iter().map(|&(_, c)| c).collect();
+                let token = match word.as_str() {
+                    "true" => Token::Literal(Value::Bool(true)),
+                    "false" => Token::Literal(Value::Bool(false)),
+                    "null" => Token::Literal(Value::Null),
+                    _ => Token::Path(word),
+                };
+                tokens.push((pos, token));
+            }
+            _ => return Err(err(pos, &format!("unexpected character '{}'", c))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> QueryError {
+        QueryError {
+            expression: self.expression.to_string(),
+            position: self
+                .tokens
+                .get(self.pos)
+                .map_or(self.expression.len(), |(p, _)| *p),
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token, what: &str) -> Result<(), QueryError> {
+        if self.peek() == Some(&expected) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected {}", what)))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Expr::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.parse_unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            let right = self.parse_unary()?;
+            left = Expr::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, QueryError> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_unary()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_path(&self, path: &str) -> Result<KeyPath, QueryError> {
+        KeyPath::parse(path).map_err(|e| self.error(&e.message))
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, QueryError> {
+        match self.peek().cloned() {
+            Some(Token::LParen) => {
+                self.pos += 1;
+                let expr = self.parse_or()?;
+                self.expect(Token::RParen, "')'")?;
+                Ok(expr)
+            }
+            Some(Token::Path(word)) if word == "exists" => {
+                self.pos += 1;
+                self.expect(Token::LParen, "'(' after exists")?;
+                let path = match self.peek().cloned() {
+                    Some(Token::Path(p)) => self.parse_path(&p)?,
+                    _ => return Err(self.error("expected a key path")),
+                };
+                self.pos += 1;
+                self.expect(Token::RParen, "')'")?;
+                Ok(Expr::Exists(path))
+            }
+            Some(Token::Path(word)) => {
+                let path = self.parse_path(&word)?;
+                self.pos += 1;
+                let Some(Token::Op(op)) = self.peek().cloned() else {
+                    return Err(self.error("expected a comparison operator"));
+                };
+                self.pos += 1;
+                let Some(Token::Literal(literal)) = self.peek().cloned() else {
+                    return Err(self.error("expected a literal value"));
+                };
+
+                let expr = match op {
+                    CompareOp::Matches | CompareOp::NotMatches => {
+                        let Value::String(pattern) = &literal else {
+                            return Err(self.error("regex must be a string"));
+                        };
+                        let regex = Regex::new(pattern).map_err(|e| self.error(&e.to_string()))?;
+                        Expr::Regex(path, op == CompareOp::NotMatches, regex)
+                    }
+                    _ => Expr::Compare(path, op, literal),
+                };
+                self.next();
+                Ok(expr)
+            }
+            _ => Err(self.error("expected a key path, exists(...) or '('")),
+        }
+    }
+}
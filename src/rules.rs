@@ -0,0 +1,417 @@
+use crate::path::{KeyPath, Segment};
+use num_format::{Locale, ToFormattedString};
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{borrow::Borrow, collections::BTreeMap, fmt, fs, io, path::Path};
+
+/// Offending rows listed per rule in the report
+const SHOWN_FAILED_ROWS: usize = 20;
+
+/// JSON type names accepted by the `type` rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonType {
+    Null,
+    Bool,
+    /// Numbers without a fractional part
+    Int,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "null" => Some(JsonType::Null),
+            "bool" | "boolean" => Some(JsonType::Bool),
+            "int" | "integer" => Some(JsonType::Int),
+            "number" => Some(JsonType::Number),
+            "string" => Some(JsonType::String),
+            "array" => Some(JsonType::Array),
+            "object" => Some(JsonType::Object),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            (JsonType::Null, Value::Null)
+            | (JsonType::Bool, Value::Bool(_))
+            | (JsonType::Number, Value::Number(_))
+            | (JsonType::String, Value::String(_))
+            | (JsonType::Array, Value::Array(_))
+            | (JsonType::Object, Value::Object(_)) => true,
+            (JsonType::Int, Value::Number(n)) => n.is_i64() || n.is_u64(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JsonType::Null => "null",
+            JsonType::Bool => "bool",
+            JsonType::Int => "int",
+            JsonType::Number => "number",
+            JsonType::String => "string",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One condition on the values at a key path
+#[derive(Debug, Clone)]
+pub enum Check {
+    /// The key must be present (a `null` value counts as present)
+    Required,
+    Type(Vec<JsonType>),
+    Allowed(Vec<Value>),
+    /// Strings matching the regular expression
+    Pattern(Regex),
+    /// Numbers within the inclusive bounds
+    Range {
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Required => write!(f, "required"),
+            Check::Type(types) => {
+                let names: Vec<String> = types.iter().map(JsonType::to_string).collect();
+                write!(f, "type {}", names.join("|"))
+            }
+            Check::Allowed(values) => {
+                let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                write!(f, "one of {}", values.join(", "))
+            }
+            Check::Pattern(regex) => write!(f, "matches /{}/", regex),
+            Check::Range { min, max } => match (min, max) {
+                (Some(min), Some(max)) => write!(f, "in {}..={}", min, max),
+                (Some(min), None) => write!(f, ">= {}", min),
+                (None, Some(max)) => write!(f, "<= {}", max),
+                (None, None) => write!(f, "any number"),
+            },
+        }
+    }
+}
+
+/// A check at a key path. Paths may use wildcards (`items[*].price`): value
+/// checks then apply to every match, and `required` to every match of the
+/// parent (each element of `items` needs a `price`).
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub path: KeyPath,
+    pub check: Check,
+    /// The key's types include `null`: the value checks (`allowed`,
+    /// `pattern`, `min`/`max`) pass over null values instead of failing them
+    pub nullable: bool,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.check)
+    }
+}
+
+impl Rule {
+    /// `Some(passed)` for a record, or `None` when there is nothing to check
+    /// (a value check on a key the record does not have).
+    pub fn evaluate(&self, record: &Value) -> Option<bool> {
+        if let Check::Required = self.check {
+            return Some(self.is_present(record));
+        }
+        let mut values = self.path.get_all(record);
+        if self.nullable && !matches!(self.check, Check::Type(_)) {
+            values.retain(|value| !value.is_null());
+        }
+        if values.is_empty() {
+            return None;
+        }
+        Some(values.into_iter().all(|value| self.check_value(value)))
+    }
+
+    fn is_present(&self, record: &Value) -> bool {
+        if !self.path.has_wildcard() {
+            return self.path.get(record).is_some();
+        }
+        let Some((last, parent)) = self.path.segments().split_last() else {
+            return true;
+        };
+        // `items.*` needs nothing of the elements themselves
+        let child = match last {
+            Segment::Wildcard => return true,
+            segment => KeyPath::from_segments(vec![segment.clone()]),
+        };
+        let parent = KeyPath::from_segments(parent.to_vec());
+        parent.expand(record).iter().all(|(parent, _)| {
+            parent
+                .get(record)
+                .and_then(|value| child.get(value))
+                .is_some()
+        })
+    }
+
+    fn check_value(&self, value: &Value) -> bool {
+        match &self.check {
+            Check::Required => true,
+            Check::Type(types) => types.iter().any(|t| t.matches(value)),
+            Check::Allowed(allowed) => allowed.contains(value),
+            Check::Pattern(regex) => value.as_str().is_some_and(|s| regex.is_match(s)),
+            Check::Range { min, max } => value
+                .as_f64()
+                .is_some_and(|n| min.is_none_or(|min| n >= min) && max.is_none_or(|max| n <= max)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// The rules of one key path as written in the rules file
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeySpec {
+    #[serde(default)]
+    required: bool,
+    #[serde(rename = "type")]
+    types: Option<OneOrMany>,
+    allowed: Option<Vec<Value>>,
+    pattern: Option<String>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    keys: BTreeMap<String, KeySpec>,
+}
+
+/// Syntax of a rules file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesFormat {
+    Json,
+    Toml,
+}
+
+impl RulesFormat {
+    /// From the file extension: `.json` or `.toml`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(RulesFormat::Json),
+            "toml" => Some(RulesFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Data quality rules per key path, read from a file such as:
+///
+/// ```toml
+/// [keys.id]
+/// required = true
+/// type = "int"
+///
+/// [keys.status]
+/// allowed = ["active", "inactive"]
+///
+/// [keys."items[*].price"]
+/// required = true
+/// min = 0
+/// ```
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let format = RulesFormat::from_path(path).ok_or_else(|| {
+            invalid_data(format!(
+                "{}: rules files must end in .json or .toml",
+                path.display()
+            ))
+        })?;
+        Self::parse(&fs::read_to_string(path)?, format)
+    }
+
+    pub fn parse(text: &str, format: RulesFormat) -> io::Result<Self> {
+        let file: RulesFile = match format {
+            RulesFormat::Json => serde_json::from_str(text).map_err(|e| e.to_string()),
+            RulesFormat::Toml => toml::from_str(text).map_err(|e| e.to_string()),
+        }
+        .map_err(|e| invalid_data(format!("invalid rules file: {}", e)))?;
+
+        let mut rules = Vec::new();
+        for (key, spec) in file.keys {
+            let path = KeyPath::parse(&key).map_err(|e| invalid_data(e.to_string()))?;
+            let types: Option<Vec<JsonType>> = spec
+                .types
+                .map(|types| {
+                    let names = match types {
+                        OneOrMany::One(name) => vec![name],
+                        OneOrMany::Many(names) => names,
+                    };
+                    names
+                        .iter()
+                        .map(|name| {
+                            JsonType::parse(name).ok_or_else(|| {
+                                invalid_data(format!("{}: unknown type '{}'", key, name))
+                            })
+                        })
+                        .collect::<io::Result<_>>()
+                })
+                .transpose()?;
+            let nullable = types
+                .as_ref()
+                .is_some_and(|types| types.contains(&JsonType::Null));
+            let mut add = |check| {
+                rules.push(Rule {
+                    path: path.clone(),
+                    check,
+                    nullable,
+                })
+            };
+            if spec.required {
+                add(Check::Required);
+            }
+            if let Some(types) = types {
+                add(Check::Type(types));
+            }
+            if let Some(values) = spec.allowed {
+                add(Check::Allowed(values));
+            }
+            if let Some(pattern) = spec.pattern {
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| invalid_data(format!("{}: invalid pattern: {}", key, e)))?;
+                add(Check::Pattern(regex));
+            }
+            if spec.min.is_some() || spec.max.is_some() {
+                add(Check::Range {
+                    min: spec.min,
+                    max: spec.max,
+                });
+            }
+        }
+        Ok(Self { rules })
+    }
+
+    /// Evaluates every rule against every record.
+    pub fn evaluate<I>(&self, records: I) -> QualityReport
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+    {
+        let mut results: Vec<RuleResult> = self
+            .rules
+            .iter()
+            .map(|rule| RuleResult {
+                rule: rule.to_string(),
+                passed: 0,
+                failed_rows: Vec::new(),
+            })
+            .collect();
+        let mut rows = 0;
+        for (row, record) in records.into_iter().enumerate() {
+            for (rule, result) in self.rules.iter().zip(&mut results) {
+                match rule.evaluate(record.borrow()) {
+                    Some(true) => result.passed += 1,
+                    Some(false) => result.failed_rows.push(row),
+                    None => {}
+                }
+            }
+            rows += 1;
+        }
+        QualityReport { rows, results }
+    }
+}
+
+/// Outcome of one rule over all records
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleResult {
+    pub rule: String,
+    pub passed: usize,
+    pub failed_rows: Vec<usize>,
+}
+
+/// Outcome of a [`RuleSet`] over all records
+#[derive(Debug, Clone, PartialEq)]
+pub struct QualityReport {
+    pub rows: usize,
+    pub results: Vec<RuleResult>,
+}
+
+impl QualityReport {
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|r| r.failed_rows.is_empty())
+    }
+
+    pub fn show_report(&self, name: &str) {
+        println!("===============================");
+        println!(
+            "Data quality of {}: {} rows, {} rules, {}",
+            name,
+            self.rows.to_formatted_string(&Locale::en),
+            self.results.len(),
+            if self.passed() { "PASSED" } else { "FAILED" }
+        );
+        let width = self
+            .results
+            .iter()
+            .map(|r| r.rule.len())
+            .max()
+            .unwrap_or(20)
+            .max(20);
+        println!(
+            "{:<width$} {:>12} {:>12}",
+            "Rule",
+            "Passed",
+            "Failed",
+            width = width
+        );
+        println!("{}", "-".repeat(width + 26));
+        for result in &self.results {
+            println!(
+                "\t{:<width$} {:>12} {:>12}",
+                result.rule,
+                result.passed.to_formatted_string(&Locale::en),
+                result.failed_rows.len().to_formatted_string(&Locale::en),
+                width = width
+            );
+            if !result.failed_rows.is_empty() {
+                let shown: Vec<String> = result
+                    .failed_rows
+                    .iter()
+                    .take(SHOWN_FAILED_ROWS)
+                    .map(usize::to_string)
+                    .collect();
+                let more = result.failed_rows.len().saturating_sub(SHOWN_FAILED_ROWS);
+                println!(
+                    "\t\trows {}{}",
+                    shown.join(", "),
+                    if more > 0 {
+                        format!(" and {} more", more)
+                    } else {
+                        String::new()
+                    }
+                );
+            }
+        }
+    }
+}
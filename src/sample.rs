@@ -0,0 +1,199 @@
+use crate::path::KeyPath;
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, io};
+
+/// Small deterministic PRNG (SplitMix64), so a seed always gives the same sample
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in `[0, n)`
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+}
+
+/// How many rows to keep
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    /// Exactly this many rows (or all of them), by reservoir sampling
+    Count(usize),
+    /// Each row independently with this probability
+    Fraction(f64),
+}
+
+/// Random sampler over a stream of records, optionally stratified by a key:
+/// with stratification each distinct key value is sampled separately, so
+/// `Count(n)` keeps up to `n` rows per stratum. Strata are named by the JSON
+/// text of the value, so `"null"` and `null` stay apart, and records without
+/// the key form their own [`MISSING_STRATUM`].
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    pub size: SampleSize,
+    pub seed: u64,
+    pub stratify: Option<KeyPath>,
+}
+
+/// Rows drawn by a [`Sampler`], in their original order
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    /// Row numbers in the input with the sampled records
+    pub rows: Vec<(usize, Value)>,
+    pub total_rows: usize,
+    /// Rows seen and kept per stratum, keyed by the JSON text of the value (a
+    /// single `*` stratum when not stratified)
+    pub strata: BTreeMap<String, (usize, usize)>,
+}
+
+/// Description of a sample, shown by the reports run on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleInfo {
+    pub sampled_rows: usize,
+    pub total_rows: usize,
+    pub method: String,
+}
+
+impl fmt::Display for SampleInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} rows, {}",
+            self.sampled_rows.to_formatted_string(&Locale::en),
+            self.total_rows.to_formatted_string(&Locale::en),
+            self.method
+        )
+    }
+}
+
+/// Name of the stratum of records without the stratification key; no JSON
+/// text can look like it
+pub const MISSING_STRATUM: &str = "(missing)";
+
+struct Stratum {
+    seen: usize,
+    kept: Vec<(usize, Value)>,
+}
+
+impl Sampler {
+    pub fn new(size: SampleSize, seed: u64) -> Self {
+        Self {
+            size,
+            seed,
+            stratify: None,
+        }
+    }
+
+    /// Draws the sample in a single pass, holding only the kept rows in memory.
+    pub fn sample<I>(&self, records: I) -> io::Result<Sample>
+    where
+        I: IntoIterator<Item = io::Result<Value>>,
+    {
+        let mut rng = Rng::new(self.seed);
+        let mut strata: BTreeMap<String, Stratum> = BTreeMap::new();
+        let mut total_rows = 0;
+
+        for (row, record) in records.into_iter().enumerate() {
+            let record = record?;
+            total_rows += 1;
+
+            let name = match &self.stratify {
+                Some(path) => path
+                    .get(&record)
+                    .map_or_else(|| MISSING_STRATUM.to_string(), Value::to_string),
+                None => "*".to_string(),
+            };
+            let stratum = strata.entry(name).or_insert_with(|| Stratum {
+                seen: 0,
+                kept: Vec::new(),
+            });
+            stratum.seen += 1;
+
+            match self.size {
+                SampleSize::Fraction(p) => {
+                    if rng.next_f64() < p {
+                        stratum.kept.push((row, record));
+                    }
+                }
+                SampleSize::Count(n) => {
+                    // Algorithm R: the i-th row replaces a random slot with probability n/i
+                    if stratum.kept.len() < n {
+                        stratum.kept.push((row, record));
+                    } else {
+                        let slot = rng.below(stratum.seen as u64) as usize;
+                        if slot < n {
+                            stratum.kept[slot] = (row, record);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut sample = Sample {
+            total_rows,
+            ..Sample::default()
+        };
+        for (name, stratum) in strata {
+            sample
+                .strata
+                .insert(name, (stratum.seen, stratum.kept.len()));
+            sample.rows.extend(stratum.kept);
+        }
+        sample.rows.sort_by_key(|(row, _)| *row);
+        Ok(sample)
+    }
+
+    pub fn describe(&self) -> String {
+        let size = match self.size {
+            SampleSize::Count(n) => format!("reservoir sample of {}", n),
+            SampleSize::Fraction(p) => format!("random {}% sample", p * 100.0),
+        };
+        match &self.stratify {
+            Some(path) => format!("{} per {} stratum, seed {}", size, path, self.seed),
+            None => format!("{}, seed {}", size, self.seed),
+        }
+    }
+}
+
+impl Sample {
+    pub fn info(&self, sampler: &Sampler) -> SampleInfo {
+        SampleInfo {
+            sampled_rows: self.rows.len(),
+            total_rows: self.total_rows,
+            method: sampler.describe(),
+        }
+    }
+
+    pub fn show_strata_report(&self) {
+        println!("===============================");
+        println!("{:<20} {:>12} {:>12}", "Stratum", "Rows", "Sampled");
+        println!("{}", "-".repeat(46));
+        for (name, (seen, kept)) in &self.strata {
+            println!(
+                "\t{:<20} {:>12} {:>12}",
+                name,
+                seen.to_formatted_string(&Locale::en),
+                kept.to_formatted_string(&Locale::en)
+            );
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::external::ExternalSorter;
+use crate::path::{KeyPath, PathError};
+use crate::value::compare_values;
+use serde_json::Value;
+use std::{
+    cmp::Ordering,
+    fmt,
+    io::{self, Write},
+};
+
+/// One sort key: a key path and a direction
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub path: KeyPath,
+    pub descending: bool,
+}
+
+/// Record ordering such as `user.id desc, ts asc`.
+///
+/// Values of different JSON types order as `null < bool < number < string <
+/// array < object`. Records missing a key sort after all others for that key,
+/// in either direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortSpec {
+    keys: Vec<SortKey>,
+}
+
+impl SortSpec {
+    pub fn parse(spec: &str) -> Result<Self, PathError> {
+        let mut keys = Vec::new();
+        for part in spec.split(',').filter(|p| !p.trim().is_empty()) {
+            let mut words = part.split_whitespace();
+            let path = KeyPath::parse_single(words.next().unwrap_or_default())?;
+            let descending = match words.next().map(str::to_ascii_lowercase).as_deref() {
+                None | Some("asc") => false,
+                Some("desc") => true,
+                Some(_) => {
+                    return Err(PathError {
+                        path: part.trim().to_string(),
+                        message: "direction must be asc or desc".to_string(),
+                    });
+                }
+            };
+            keys.push(SortKey { path, descending });
+        }
+
+        if keys.is_empty() {
+            return Err(PathError {
+                path: spec.to_string(),
+                message: "no sort keys given".to_string(),
+            });
+        }
+        Ok(Self { keys })
+    }
+
+    pub fn keys(&self) -> &[SortKey] {
+        &self.keys
+    }
+
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        for key in &self.keys {
+            let ordering = match (key.path.get(a), key.path.get(b)) {
+                (Some(x), Some(y)) if key.descending => compare_values(y, x),
+                (Some(x), Some(y)) => compare_values(x, y),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Sorts records with at most `run_size` of them in memory at once,
+    /// spilling sorted runs to temp files, and writes the result as JSONL.
+    /// The sort is stable.
+    pub fn sort<I, W>(&self, records: I, run_size: usize, mut writer: W) -> io::Result<SortSummary>
+    where
+        I: IntoIterator<Item = io::Result<Value>>,
+        W: Write,
+    {
+        let mut sorter = ExternalSorter::new(run_size, |a: &Value, b: &Value| self.compare(a, b))?;
+        for record in records {
+            sorter.push(record?)?;
+        }
+        let sorted = sorter.finish()?;
+        let runs = sorted.runs();
+
+        let mut rows = 0;
+        for record in sorted {
+            serde_json::to_writer(&mut writer, &record?)?;
+            writer.write_all(b"\n")?;
+            rows += 1;
+        }
+        writer.flush()?;
+
+        Ok(SortSummary { rows, runs })
+    }
+}
+
+impl fmt::Display for SortSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|k| format!("{} {}", k.path, if k.descending { "desc" } else { "asc" }))
+            .collect();
+        write!(f, "{}", keys.join(", "))
+    }
+}
+
+/// Outcome of an external sort
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortSummary {
+    pub rows: usize,
+    /// Sorted runs spilled to disk; 0 when the input fit in memory
+    pub runs: usize,
+}
@@ -0,0 +1,252 @@
+use crate::path::KeyPath;
+use crate::value::display_value;
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// How records are distributed over output files
+#[derive(Debug, Clone, PartialEq)]
+pub enum SplitMode {
+    /// Chunks of at most this many records
+    Rows(usize),
+    /// Chunks of at most this many bytes (a single larger record gets its own chunk)
+    Bytes(u64),
+    /// One file per distinct value of the key path
+    Key(KeyPath),
+}
+
+/// Splits or partitions a stream of records into several JSONL files.
+///
+/// Output names come from a template with the placeholders `{name}` (stem of
+/// the source name), `{index}` (chunk number, from 0) and `{key}` (partition
+/// value, `null` when missing, with path separators replaced). Chunks need
+/// `{index}` and partitions `{key}` in the template, so that every output gets
+/// its own file.
+#[derive(Debug, Clone)]
+pub struct Splitter {
+    pub mode: SplitMode,
+    pub template: String,
+    /// Upper bound on output files held open at once when partitioning by key
+    pub max_open: usize,
+}
+
+/// Files written by a split, with the number of records in each
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SplitSummary {
+    pub files: BTreeMap<PathBuf, usize>,
+}
+
+impl Splitter {
+    pub fn new(mode: SplitMode) -> Self {
+        let template = match mode {
+            SplitMode::Key(_) => "{name}-{key}.jsonl",
+            _ => "{name}-{index}.jsonl",
+        };
+        Self {
+            mode,
+            template: template.to_string(),
+            max_open: 64,
+        }
+    }
+
+    fn output_path(&self, name: &str, index: usize, key: &str) -> PathBuf {
+        PathBuf::from(
+            self.template
+                .replace("{name}", name)
+                .replace("{index}", &index.to_string())
+                .replace("{key}", key),
+        )
+    }
+
+    /// Splits `records` from the source `name` according to the mode.
+    pub fn split<I>(&self, name: &str, records: I) -> io::Result<SplitSummary>
+    where
+        I: IntoIterator<Item = io::Result<Value>>,
+    {
+        let placeholder = match self.mode {
+            SplitMode::Rows(_) | SplitMode::Bytes(_) => "{index}",
+            SplitMode::Key(_) => "{key}",
+        };
+        if !self.template.contains(placeholder) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "output template '{}' lacks {}, so all outputs would go to one file",
+                    self.template, placeholder
+                ),
+            ));
+        }
+
+        let stem = Path::new(name)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.to_string());
+
+        match &self.mode {
+            SplitMode::Rows(_) | SplitMode::Bytes(_) => self.split_chunks(&stem, records),
+            SplitMode::Key(path) => self.partition(&stem, path, records),
+        }
+    }
+
+    fn split_chunks<I>(&self, stem: &str, records: I) -> io::Result<SplitSummary>
+    where
+        I: IntoIterator<Item = io::Result<Value>>,
+    {
+        let mut summary = SplitSummary::default();
+        let mut current: Option<(PathBuf, BufWriter<File>)> = None;
+        let mut index = 0;
+        let mut rows_in_chunk = 0;
+        let mut bytes_in_chunk: u64 = 0;
+
+        for record in records {
+            let mut line = serde_json::to_string(&record?)?;
+            line.push('\n');
+            let len = line.len() as u64;
+
+            let full = match self.mode {
+                SplitMode::Rows(max) => rows_in_chunk >= max.max(1),
+                SplitMode::Bytes(max) => rows_in_chunk > 0 && bytes_in_chunk + len > max,
+                SplitMode::Key(_) => false,
+            };
+            if full && let Some((_, mut writer)) = current.take() {
+                writer.flush()?;
+                index += 1;
+            }
+
+            if current.is_none() {
+                let path = self.output_path(stem, index, "");
+                current = Some((path.clone(), create_output(&path)?));
+                summary.files.insert(path, 0);
+                rows_in_chunk = 0;
+                bytes_in_chunk = 0;
+            }
+
+            if let Some((path, writer)) = current.as_mut() {
+                writer.write_all(line.as_bytes())?;
+                *summary.files.entry(path.clone()).or_default() += 1;
+            }
+            rows_in_chunk += 1;
+            bytes_in_chunk += len;
+        }
+
+        if let Some((_, mut writer)) = current {
+            writer.flush()?;
+        }
+        Ok(summary)
+    }
+
+    fn partition<I>(&self, stem: &str, path: &KeyPath, records: I) -> io::Result<SplitSummary>
+    where
+        I: IntoIterator<Item = io::Result<Value>>,
+    {
+        let mut summary = SplitSummary::default();
+        let mut open: HashMap<PathBuf, BufWriter<File>> = HashMap::new();
+        // Least recently used first
+        let mut usage: VecDeque<PathBuf> = VecDeque::new();
+        // The partition value (`None` when missing or null) each file was created for
+        let mut created: HashMap<PathBuf, Option<Value>> = HashMap::new();
+
+        for record in records {
+            let record = record?;
+            let value = path.get(&record).filter(|v| !v.is_null());
+            let key = value.map_or_else(|| "null".to_string(), display_value);
+            let output = self.output_path(stem, 0, &sanitize(&key));
+
+            match created.get(&output) {
+                Some(owner) if owner.as_ref() != value => {
+                    let show =
+                        |v: Option<&Value>| v.map_or_else(|| "null".to_string(), Value::to_string);
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "partition values {} and {} both map to {}",
+                            show(owner.as_ref()),
+                            show(value),
+                            output.display()
+                        ),
+                    ));
+                }
+                Some(_) => {}
+                None => {
+                    created.insert(output.clone(), value.cloned());
+                }
+            }
+
+            if !open.contains_key(&output) {
+                if open.len() >= self.max_open.max(1)
+                    && let Some(oldest) = usage.pop_front()
+                    && let Some(mut writer) = open.remove(&oldest)
+                {
+                    writer.flush()?;
+                }
+                // Files closed earlier to respect the cap are reopened for appending
+                let writer = if summary.files.contains_key(&output) {
+                    BufWriter::new(OpenOptions::new().append(true).open(&output)?)
+                } else {
+                    create_output(&output)?
+                };
+                open.insert(output.clone(), writer);
+            } else if let Some(pos) = usage.iter().position(|p| *p == output) {
+                usage.remove(pos);
+            }
+            usage.push_back(output.clone());
+
+            if let Some(writer) = open.get_mut(&output) {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writer.write_all(b"\n")?;
+            }
+            *summary.files.entry(output).or_default() += 1;
+        }
+
+        for writer in open.values_mut() {
+            writer.flush()?;
+        }
+        Ok(summary)
+    }
+}
+
+fn create_output(path: &Path) -> io::Result<BufWriter<File>> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(BufWriter::new(File::create(path)?))
+}
+
+/// Makes a partition value safe to use inside a file name.
+fn sanitize(key: &str) -> String {
+    let cleaned: String = key
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    match cleaned.as_str() {
+        "" | "." | ".." => "_".to_string(),
+        _ => cleaned,
+    }
+}
+
+impl SplitSummary {
+    pub fn show_report(&self, source_name: &str) {
+        println!("===============================");
+        println!(
+            "Split {} into {} files",
+            source_name,
+            self.files.len().to_formatted_string(&Locale::en)
+        );
+        for (path, rows) in &self.files {
+            println!(
+                "\t{:<40} {:>12}",
+                path.display(),
+                rows.to_formatted_string(&Locale::en)
+            );
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use crate::path::{KeyPath, walk_paths};
+use chrono::{DateTime, DurationRound, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+/// Share of the non-null values of a key that must parse for it to be detected
+const DETECTION_THRESHOLD: f64 = 0.95;
+/// Share of the values of a key named with `--key` that decides its format
+const MAJORITY: f64 = 0.5;
+/// Most histogram bins; longer spans group several buckets per bin
+const MAX_HISTOGRAM_BINS: i64 = 500;
+/// Epoch values are only recognized between 2000-01-01 and 2100-01-01
+const EPOCH_SECONDS_RANGE: (f64, f64) = (946_684_800.0, 4_102_444_800.0);
+/// Width of the longest histogram bar
+const BAR_WIDTH: usize = 50;
+/// Gaps and out-of-order rows listed in the report
+const SHOWN_ENTRIES: usize = 10;
+
+/// Parses RFC 3339 and the common ISO 8601 forms; times without an offset are taken as UTC.
+pub fn parse_iso(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.with_timezone(&Utc));
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .map(|time| time.and_utc())
+}
+
+/// Parses durations such as `90s`, `5m`, `1h` or `2d`.
+pub fn parse_duration(text: &str) -> Option<TimeDelta> {
+    let split = text.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = text.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    match unit {
+        "s" => TimeDelta::try_seconds(number),
+        "m" => TimeDelta::try_minutes(number),
+        "h" => TimeDelta::try_hours(number),
+        "d" => TimeDelta::try_days(number),
+        _ => None,
+    }
+}
+
+fn format_duration(delta: TimeDelta) -> String {
+    let secs = delta.num_seconds();
+    match secs {
+        s if s >= 86_400 => format!("{}d {}h", s / 86_400, s % 86_400 / 3_600),
+        s if s >= 3_600 => format!("{}h {}m", s / 3_600, s % 3_600 / 60),
+        s if s >= 60 => format!("{}m {}s", s / 60, s % 60),
+        s => format!("{}s", s),
+    }
+}
+
+/// How a key stores its timestamps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TimeFormat {
+    /// RFC 3339 / ISO 8601 strings
+    Iso,
+    EpochSeconds,
+    EpochMillis,
+}
+
+impl fmt::Display for TimeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeFormat::Iso => write!(f, "ISO 8601"),
+            TimeFormat::EpochSeconds => write!(f, "epoch seconds"),
+            TimeFormat::EpochMillis => write!(f, "epoch milliseconds"),
+        }
+    }
+}
+
+impl TimeFormat {
+    pub fn parse(&self, value: &Value) -> Option<DateTime<Utc>> {
+        let (min, max) = EPOCH_SECONDS_RANGE;
+        match (self, value) {
+            (TimeFormat::Iso, Value::String(s)) => parse_iso(s),
+            (TimeFormat::EpochSeconds, Value::Number(n)) => {
+                let secs = n.as_f64().filter(|s| (min..max).contains(s))?;
+                DateTime::from_timestamp_millis((secs * 1000.0).round() as i64)
+            }
+            (TimeFormat::EpochMillis, Value::Number(n)) => {
+                let millis = n
+                    .as_i64()
+                    .filter(|ms| (min..max).contains(&(*ms as f64 / 1000.0)))?;
+                DateTime::from_timestamp_millis(millis)
+            }
+            _ => None,
+        }
+    }
+
+    /// The format `value` parses in, if any
+    fn of(value: &Value) -> Option<Self> {
+        [
+            TimeFormat::Iso,
+            TimeFormat::EpochSeconds,
+            TimeFormat::EpochMillis,
+        ]
+        .into_iter()
+        .find(|format| format.parse(value).is_some())
+    }
+}
+
+/// Non-null values and values per time format, per key path
+#[derive(Debug, Default)]
+struct KeyCounts {
+    values: usize,
+    formats: HashMap<TimeFormat, usize>,
+}
+
+impl KeyCounts {
+    fn add(&mut self, leaf: &Value) {
+        if leaf.is_null() || leaf.is_object() || leaf.is_array() {
+            return;
+        }
+        self.values += 1;
+        if let Some(format) = TimeFormat::of(leaf) {
+            *self.formats.entry(format).or_insert(0) += 1;
+        }
+    }
+
+    /// The most common format, if more than `threshold` of the values have it
+    /// (at least all of them when `threshold` is 1)
+    fn format(self, threshold: f64) -> Option<TimeFormat> {
+        let (format, matched) = self
+            .formats
+            .into_iter()
+            .max_by_key(|(format, n)| (*n, std::cmp::Reverse(*format)))?;
+        (matched as f64 >= self.values as f64 * threshold).then_some(format)
+    }
+}
+
+/// Counts the non-null scalar values of every key path and the timestamp
+/// formats among them; array elements share one path (`events[].ts`).
+fn count_leaf_formats(value: &Value, counts: &mut BTreeMap<String, KeyCounts>) {
+    walk_paths(value, &mut |path, leaf| {
+        if !leaf.is_object() && !leaf.is_array() {
+            counts.entry(path.to_string()).or_default().add(leaf);
+        }
+    });
+}
+
+/// The format of most non-null values at `path` (every match when it has
+/// wildcards), for a key named explicitly rather than detected
+pub fn majority_format<I>(records: I, path: &KeyPath) -> Option<TimeFormat>
+where
+    I: IntoIterator,
+    I::Item: Borrow<Value>,
+{
+    let mut counts = KeyCounts::default();
+    for record in records {
+        for value in path.get_all(record.borrow()) {
+            counts.add(value);
+        }
+    }
+    counts.format(MAJORITY)
+}
+
+/// Key paths whose non-null values are nearly all timestamps, with their format
+pub fn detect_timestamp_keys<I>(records: I) -> Vec<(String, TimeFormat)>
+where
+    I: IntoIterator,
+    I::Item: Borrow<Value>,
+{
+    let mut counts = BTreeMap::new();
+    for record in records {
+        count_leaf_formats(record.borrow(), &mut counts);
+    }
+    counts
+        .into_iter()
+        .filter_map(|(path, counts)| Some((path, counts.format(DETECTION_THRESHOLD)?)))
+        .collect()
+}
+
+/// Width of a histogram bin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bucket {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "minute" => Some(Bucket::Minute),
+            "hour" => Some(Bucket::Hour),
+            "day" => Some(Bucket::Day),
+            _ => None,
+        }
+    }
+
+    /// The finest bucket giving at most a few hundred bins over `span`
+    pub fn for_span(span: TimeDelta) -> Self {
+        if span <= TimeDelta::hours(6) {
+            Bucket::Minute
+        } else if span <= TimeDelta::days(14) {
+            Bucket::Hour
+        } else {
+            Bucket::Day
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Bucket::Minute => "minute",
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+        }
+    }
+
+    fn width(&self) -> TimeDelta {
+        match self {
+            Bucket::Minute => TimeDelta::minutes(1),
+            Bucket::Hour => TimeDelta::hours(1),
+            Bucket::Day => TimeDelta::days(1),
+        }
+    }
+
+    fn label(&self, start: DateTime<Utc>) -> String {
+        let format = match self {
+            Bucket::Minute => "%Y-%m-%d %H:%M",
+            Bucket::Hour => "%Y-%m-%d %H:00",
+            Bucket::Day => "%Y-%m-%d",
+        };
+        start.format(format).to_string()
+    }
+}
+
+/// A stretch without records longer than the gap threshold
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gap {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+}
+
+/// Time range, gaps, ordering and volume over time of one timestamp key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timeline {
+    pub path: String,
+    pub format: TimeFormat,
+    /// Rows with a parsable timestamp
+    pub rows: usize,
+    /// Rows where the key is absent, null or not a timestamp
+    pub unparsable_rows: Vec<usize>,
+    pub min: Option<DateTime<Utc>>,
+    pub max: Option<DateTime<Utc>>,
+    /// Rows with a timestamp earlier than the one before it
+    pub out_of_order_rows: Vec<usize>,
+    pub gap_threshold: TimeDelta,
+    pub gaps: Vec<Gap>,
+    pub bucket: Bucket,
+    /// Buckets per histogram bin, above 1 when the span would need more
+    /// than `MAX_HISTOGRAM_BINS` bins
+    pub buckets_per_bin: i64,
+    /// Start of each bin with its count of timestamps, empty bins included
+    pub histogram: Vec<(DateTime<Utc>, usize)>,
+}
+
+impl Timeline {
+    /// Analyzes the key at `path`, every match of it when it has wildcards;
+    /// without `bucket` one is picked for the time span.
+    pub fn analyze<I>(
+        records: I,
+        path: &KeyPath,
+        format: TimeFormat,
+        gap_threshold: TimeDelta,
+        bucket: Option<Bucket>,
+    ) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Value>,
+    {
+        let mut times = Vec::new();
+        let mut rows = 0;
+        let mut unparsable_rows = Vec::new();
+        let mut out_of_order_rows = Vec::new();
+        let mut previous: Option<DateTime<Utc>> = None;
+        for (row, record) in records.into_iter().enumerate() {
+            let row_times: Vec<DateTime<Utc>> = path
+                .get_all(record.borrow())
+                .into_iter()
+                .filter_map(|value| format.parse(value))
+                .collect();
+            if row_times.is_empty() {
+                unparsable_rows.push(row);
+                continue;
+            }
+            rows += 1;
+            let mut out_of_order = false;
+            for time in row_times {
+                out_of_order |= previous.is_some_and(|previous| time < previous);
+                previous = Some(time);
+                times.push(time);
+            }
+            if out_of_order {
+                out_of_order_rows.push(row);
+            }
+        }
+        times.sort();
+
+        let gaps = times
+            .windows(2)
+            .filter(|pair| pair[1] - pair[0] > gap_threshold)
+            .map(|pair| Gap {
+                from: pair[0],
+                to: pair[1],
+            })
+            .collect();
+
+        let (min, max) = (times.first().copied(), times.last().copied());
+        let bucket = bucket.unwrap_or_else(|| match (min, max) {
+            (Some(min), Some(max)) => Bucket::for_span(max - min),
+            _ => Bucket::Hour,
+        });
+        let mut buckets_per_bin = 1;
+        let mut histogram = Vec::new();
+        if let (Some(min), Some(max)) = (min, max) {
+            let start = min.duration_trunc(bucket.width()).unwrap_or(min);
+            let bucket_seconds = bucket.width().num_seconds();
+            let buckets = (max - start).num_seconds() / bucket_seconds + 1;
+            buckets_per_bin = (buckets + MAX_HISTOGRAM_BINS - 1) / MAX_HISTOGRAM_BINS;
+            let bin_seconds = bucket_seconds * buckets_per_bin;
+            let mut counts = vec![0; ((buckets - 1) / buckets_per_bin + 1) as usize];
+            for time in &times {
+                counts[((*time - start).num_seconds() / bin_seconds) as usize] += 1;
+            }
+            histogram = counts
+                .into_iter()
+                .enumerate()
+                .map(|(bin, count)| (start + TimeDelta::seconds(bin as i64 * bin_seconds), count))
+                .collect();
+        }
+
+        Self {
+            path: path.to_string(),
+            format,
+            rows,
+            unparsable_rows,
+            min,
+            max,
+            out_of_order_rows,
+            gap_threshold,
+            gaps,
+            bucket,
+            buckets_per_bin,
+            histogram,
+        }
+    }
+
+    pub fn show_report(&self) {
+        println!("===============================");
+        println!(
+            "Timestamps in {} ({}): {} rows, {} without a timestamp",
+            self.path,
+            self.format,
+            self.rows.to_formatted_string(&Locale::en),
+            self.unparsable_rows.len().to_formatted_string(&Locale::en)
+        );
+        let (Some(min), Some(max)) = (self.min, self.max) else {
+            return;
+        };
+        println!(
+            "Range: {} .. {} ({})",
+            min.to_rfc3339(),
+            max.to_rfc3339(),
+            format_duration(max - min)
+        );
+
+        println!(
+            "Out-of-order rows: {}",
+            self.out_of_order_rows
+                .len()
+                .to_formatted_string(&Locale::en)
+        );
+        if !self.out_of_order_rows.is_empty() {
+            let rows: Vec<String> = self
+                .out_of_order_rows
+                .iter()
+                .take(SHOWN_ENTRIES)
+                .map(usize::to_string)
+                .collect();
+            println!("\trows {}", rows.join(", "));
+        }
+
+        println!(
+            "Gaps longer than {}: {}",
+            format_duration(self.gap_threshold),
+            self.gaps.len().to_formatted_string(&Locale::en)
+        );
+        for gap in self.gaps.iter().take(SHOWN_ENTRIES) {
+            println!(
+                "\t{} .. {} ({})",
+                gap.from.to_rfc3339(),
+                gap.to.to_rfc3339(),
+                format_duration(gap.to - gap.from)
+            );
+        }
+
+        if self.buckets_per_bin > 1 {
+            println!(
+                "Records per {} {}s:",
+                self.buckets_per_bin,
+                self.bucket.name()
+            );
+        } else {
+            println!("Records per {}:", self.bucket.name());
+        }
+        let peak = self.histogram.iter().map(|(_, n)| *n).max().unwrap_or(0);
+        for (start, count) in &self.histogram {
+            let bar = if peak == 0 {
+                0
+            } else {
+                // Any record gets at least one mark, so only empty bins are blank
+                (count * BAR_WIDTH).div_ceil(peak)
+            };
+            println!(
+                "\t{} {:>10} {}",
+                self.bucket.label(*start),
+                count.to_formatted_string(&Locale::en),
+                "#".repeat(bar)
+            );
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use crate::path::{KeyPath, PathError, Segment};
+use num_format::{Locale, ToFormattedString};
+use serde_json::Value;
+use std::fmt::{self, Write as _};
+
+/// A single bulk key transformation, written on the command line as
+/// `rename:FROM=NEW_NAME`, `move:FROM=TO` or `delete:PATH`.
+///
+/// Paths use the key report notation and may contain `*` wildcards. A `*` in
+/// a rename target is replaced by the matched key name; each `*` in a move
+/// target (a whole segment or part of a key name) is replaced by the key or
+/// index the corresponding source wildcard matched.
+#[derive(Debug, Clone)]
+pub enum TransformRule {
+    /// Renames the final key in place, keeping it at the same nesting level
+    Rename {
+        from: KeyPath,
+        to: String,
+    },
+    /// Moves the value to a new path, possibly at a different nesting level;
+    /// objects left empty behind it are removed as with `Delete`
+    Move {
+        from: KeyPath,
+        to: KeyPath,
+    },
+    /// Removes the matched values; objects left empty by the removal are
+    /// removed too, so `delete:debug.*` leaves no `"debug": {}` behind
+    Delete {
+        path: KeyPath,
+    },
+}
+
+impl TransformRule {
+    pub fn parse(spec: &str) -> Result<Self, PathError> {
+        let err = |message: &str| PathError {
+            path: spec.to_string(),
+            message: message.to_string(),
+        };
+
+        let (op, args) = spec
+            .split_once(':')
+            .ok_or_else(|| err("expected rename:FROM=TO, move:FROM=TO or delete:PATH"))?;
+
+        match op {
+            "delete" => Ok(TransformRule::Delete {
+                path: KeyPath::parse(args)?,
+            }),
+            "rename" | "move" => {
+                let (from, to) = args
+                    .split_once('=')
+                    .ok_or_else(|| err("expected FROM=TO"))?;
+                let from = KeyPath::parse(from)?;
+                if op == "rename" {
+                    if to.is_empty() || to.contains(['.', '[']) {
+                        return Err(err("rename target must be a single key name"));
+                    }
+                    Ok(TransformRule::Rename {
+                        from,
+                        to: to.to_string(),
+                    })
+                } else {
+                    let to = KeyPath::parse(to)?;
+                    let wildcards = |p: &KeyPath| {
+                        p.segments()
+                            .iter()
+                            .filter(|s| match s {
+                                Segment::Wildcard => true,
+                                Segment::Key(k) => k.contains('*'),
+                                Segment::Index(_) => false,
+                            })
+                            .count()
+                    };
+                    if wildcards(&to) > wildcards(&from) {
+                        return Err(err("move target has more '*' than the source path"));
+                    }
+                    Ok(TransformRule::Move { from, to })
+                }
+            }
+            _ => Err(err("unknown operation (use rename, move or delete)")),
+        }
+    }
+
+    /// Applies the rule to one record. A rename or move whose target already
+    /// exists is refused and counted as a collision instead of overwriting it.
+    pub fn apply(&self, record: &mut Value) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+        // Reverse order so removing array elements does not shift later matches
+        let mut removed = Vec::new();
+        match self {
+            TransformRule::Delete { path } => {
+                for (concrete, _) in path.expand(record).into_iter().rev() {
+                    if concrete.remove(record).is_some() {
+                        outcome.changed += 1;
+                        removed.push(concrete);
+                    }
+                }
+            }
+            TransformRule::Rename { from, to } => {
+                for (concrete, _) in from.expand(record).into_iter().rev() {
+                    let mut segments = concrete.segments().to_vec();
+                    let Some(Segment::Key(old_name)) = segments.pop() else {
+                        continue;
+                    };
+                    segments.push(Segment::Key(to.replace('*', &old_name)));
+                    let target = KeyPath::from_segments(segments);
+                    outcome.add(relocate(record, &concrete, &target));
+                }
+            }
+            TransformRule::Move { from, to } => {
+                for (concrete, captures) in from.expand(record).into_iter().rev() {
+                    let mut captures = captures.into_iter();
+                    let segments = to
+                        .segments()
+                        .iter()
+                        .map(|s| match s {
+                            Segment::Wildcard => captures.next().unwrap_or(Segment::Wildcard),
+                            Segment::Key(k) if k.contains('*') => {
+                                let matched = match captures.next() {
+                                    Some(Segment::Key(name)) => name,
+                                    Some(Segment::Index(i)) => i.to_string(),
+                                    _ => "*".to_string(),
+                                };
+                                Segment::Key(k.replace('*', &matched))
+                            }
+                            other => other.clone(),
+                        })
+                        .collect();
+                    let target = KeyPath::from_segments(segments);
+                    if outcome.add(relocate(record, &concrete, &target)) {
+                        removed.push(concrete);
+                    }
+                }
+            }
+        }
+        for path in removed {
+            prune_empty_parents(record, &path);
+        }
+        outcome
+    }
+}
+
+/// What one rule did to one record
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuleOutcome {
+    /// Keys renamed, moved or deleted
+    pub changed: usize,
+    /// Renames or moves refused because the target already held a value
+    pub collisions: usize,
+}
+
+impl RuleOutcome {
+    /// Counts one relocation, returning whether the value was moved.
+    fn add(&mut self, relocated: Relocated) -> bool {
+        match relocated {
+            Relocated::Moved => self.changed += 1,
+            Relocated::Collision => self.collisions += 1,
+            Relocated::Skipped => {}
+        }
+        relocated == Relocated::Moved
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Relocated {
+    Moved,
+    /// The target already exists and was left untouched
+    Collision,
+    Skipped,
+}
+
+/// Removes the objects above `removed` that its removal left empty, stopping
+/// at array elements so that later indices do not shift.
+fn prune_empty_parents(record: &mut Value, removed: &KeyPath) {
+    let mut segments = removed.segments().to_vec();
+    segments.pop();
+    while let Some(Segment::Key(_)) = segments.last() {
+        let parent = KeyPath::from_segments(segments.clone());
+        match parent.get(record) {
+            Some(Value::Object(map)) if map.is_empty() => {
+                parent.remove(record);
+            }
+            _ => break,
+        }
+        segments.pop();
+    }
+}
+
+/// Moves the value at `from` to `to`, restoring it if the target cannot be
+/// created. An existing target is never overwritten.
+fn relocate(record: &mut Value, from: &KeyPath, to: &KeyPath) -> Relocated {
+    if from == to || to.has_wildcard() || from.get(record).is_none() {
+        return Relocated::Skipped;
+    }
+    if to.get(record).is_some() {
+        return Relocated::Collision;
+    }
+    let Some(value) = from.remove(record) else {
+        return Relocated::Skipped;
+    };
+    if to.insert(record, value.clone()) {
+        Relocated::Moved
+    } else {
+        from.insert(record, value);
+        Relocated::Skipped
+    }
+}
+
+impl fmt::Display for TransformRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransformRule::Rename { from, to } => write!(f, "rename:{}={}", from, to),
+            TransformRule::Move { from, to } => write!(f, "move:{}={}", from, to),
+            TransformRule::Delete { path } => write!(f, "delete:{}", path),
+        }
+    }
+}
+
+/// An ordered list of rules applied to every record
+#[derive(Debug, Clone, Default)]
+pub struct Transform {
+    rules: Vec<TransformRule>,
+}
+
+impl Transform {
+    pub fn new(rules: Vec<TransformRule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn parse<S: AsRef<str>>(specs: &[S]) -> Result<Self, PathError> {
+        let rules = specs
+            .iter()
+            .map(|s| TransformRule::parse(s.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn rules(&self) -> &[TransformRule] {
+        &self.rules
+    }
+
+    /// Applies every rule in order, returning what each rule did.
+    pub fn apply(&self, record: &mut Value) -> Vec<RuleOutcome> {
+        self.rules.iter().map(|rule| rule.apply(record)).collect()
+    }
+}
+
+/// Per-rule outcome of running a [`Transform`] over a dataset
+#[derive(Debug, Clone)]
+pub struct TransformSummary {
+    pub rules: Vec<String>,
+    /// Number of rows each rule touched (same order as `rules`)
+    pub rows_touched: Vec<usize>,
+    /// Number of keys each rule left in place because its target existed
+    pub collisions: Vec<usize>,
+    pub rows_changed: usize,
+    pub rows_total: usize,
+    pub dry_run: bool,
+}
+
+impl TransformSummary {
+    pub fn new(transform: &Transform, dry_run: bool) -> Self {
+        Self {
+            rules: transform.rules().iter().map(|r| r.to_string()).collect(),
+            rows_touched: vec![0; transform.rules().len()],
+            collisions: vec![0; transform.rules().len()],
+            rows_changed: 0,
+            rows_total: 0,
+            dry_run,
+        }
+    }
+
+    /// Accumulates the per-rule outcomes of one row.
+    pub fn record_row(&mut self, outcomes: &[RuleOutcome]) {
+        self.rows_total += 1;
+        for (i, outcome) in outcomes.iter().enumerate() {
+            if outcome.changed > 0 {
+                self.rows_touched[i] += 1;
+            }
+            self.collisions[i] += outcome.collisions;
+        }
+        if outcomes.iter().any(|o| o.changed > 0) {
+            self.rows_changed += 1;
+        }
+    }
+
+    pub fn show_report(&self, source_name: &str) {
+        print!("{}", self.report(source_name));
+    }
+
+    /// The text of [`TransformSummary::show_report`], for printing somewhere
+    /// other than stdout when the transformed records are written there.
+    pub fn report(&self, source_name: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "===============================");
+        let _ = writeln!(
+            out,
+            "Transform {}on {}: {} of {} rows changed",
+            if self.dry_run { "dry run " } else { "" },
+            source_name,
+            self.rows_changed.to_formatted_string(&Locale::en),
+            self.rows_total.to_formatted_string(&Locale::en)
+        );
+
+        let max_rule_len = self
+            .rules
+            .iter()
+            .map(|r| r.len())
+            .max()
+            .unwrap_or(20)
+            .max(20);
+
+        let _ = writeln!(
+            out,
+            "{:<width$} {:>12} {:>12}",
+            "Rule",
+            "Rows",
+            "Collisions",
+            width = max_rule_len
+        );
+        let _ = writeln!(out, "{}", "-".repeat(max_rule_len + 27));
+        for ((rule, touched), collisions) in self
+            .rules
+            .iter()
+            .zip(&self.rows_touched)
+            .zip(&self.collisions)
+        {
+            let fmt_touched = touched.to_formatted_string(&Locale::en);
+            let fmt_collisions = collisions.to_formatted_string(&Locale::en);
+            let _ = writeln!(
+                out,
+                "\t{:<width$} {:>12} {:>12}",
+                rule,
+                fmt_touched,
+                fmt_collisions,
+                width = max_rule_len
+            );
+        }
+        out
+    }
+}
@@ -0,0 +1,66 @@
+use serde_json::{Number, Value};
+use std::cmp::Ordering;
+
+/// Rank of each JSON type in the total order used by [`compare_values`]
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+/// Total order over JSON values: `null < bool < number < string < array < object`.
+/// Numbers compare numerically, strings lexically, arrays element by element
+/// and objects by their (key-sorted) serialization.
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(x), Value::Bool(y)) => x.cmp(y),
+        (Value::Number(x), Value::Number(y)) => compare_numbers(x, y),
+        (Value::String(x), Value::String(y)) => x.cmp(y),
+        (Value::Array(x), Value::Array(y)) => {
+            for (xv, yv) in x.iter().zip(y) {
+                let ordering = compare_values(xv, yv);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            x.len().cmp(&y.len())
+        }
+        (Value::Object(_), Value::Object(_)) => a.to_string().cmp(&b.to_string()),
+        _ => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+fn compare_numbers(x: &Number, y: &Number) -> Ordering {
+    // Exact comparison when both fit in an integer type, f64 otherwise
+    if let (Some(a), Some(b)) = (x.as_i64(), y.as_i64()) {
+        return a.cmp(&b);
+    }
+    if let (Some(a), Some(b)) = (x.as_u64(), y.as_u64()) {
+        return a.cmp(&b);
+    }
+    let a = x.as_f64().unwrap_or(f64::NAN);
+    let b = y.as_f64().unwrap_or(f64::NAN);
+    a.total_cmp(&b)
+}
+
+/// Converts a computed number back to JSON, as an integer when it is one.
+pub fn number_value(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+        Value::from(n as i64)
+    } else {
+        Number::from_f64(n).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// Renders a value for a table cell: strings without quotes, the rest as JSON.
+pub fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}